@@ -1,34 +1,77 @@
 use crate::comms::MessageFromLV;
+use crate::exit_codes::SIGINT;
+use crate::signal_loop::SignalAction;
 use log::{debug, error};
+use std::collections::VecDeque;
 use std::error::Error;
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// The number of lines of output we keep to show when the app goes idle.
+const IDLE_CONTEXT_LINES: usize = 10;
 
 pub enum ActionMessage {
     LVMessage(MessageFromLV),
     CommsError(Box<dyn Error + Send + Sync>),
     CtrlC,
+    /// A termination signal other than Ctrl+C, by number, with the action configured for it.
+    Signal(i32, SignalAction),
+    /// The application process has gone.
+    ProcessExited,
+    /// The run has gone on longer than the run timeout.
+    RunTimeout,
 }
 
 pub enum ExitAction {
     /// Exit cleanly with the provided error code.
     CleanExit(i32),
     /// Kill signals have been recieved. Stop and kill all processes ASAP.
-    ForcedExit,
+    ForcedExit(i32),
+    /// A signal asked us to cancel. Terminate LabVIEW before killing it.
+    Cancelled(i32),
+    /// A signal asked us to stop without closing LabVIEW.
+    Detached(i32),
+    /// The run timed out. Cancel LabVIEW then kill it according to the kill policy.
+    TimedOut,
+    /// The app sent nothing for the idle timeout. Stop LabVIEW as for a run timeout.
+    IdleTimeout,
+    /// The connection to the application failed before it sent an exit code.
+    CommsFailed,
+    /// The application terminated before sending an exit code.
+    AppTerminated,
+}
+
+impl ExitAction {
+    /// The exit for a signal given the action configured for it.
+    pub fn for_signal(signal: i32, action: SignalAction) -> Self {
+        match action {
+            SignalAction::Cancel => ExitAction::Cancelled(signal),
+            SignalAction::Kill => ExitAction::ForcedExit(signal),
+            SignalAction::Detach => ExitAction::Detached(signal),
+        }
+    }
 }
 
 pub struct ActionLoop {
     tx: Sender<ActionMessage>,
     rx: Receiver<ActionMessage>,
     stopped: Arc<AtomicBool>,
+    /// If Some, the longest the app can go without sending a message once the loop is running.
+    idle_timeout: Option<Duration>,
 }
 
 impl ActionLoop {
-    pub fn new() -> Self {
+    pub fn new(idle_timeout: Option<Duration>) -> Self {
         let (tx, rx) = mpsc::channel();
         let stopped = Arc::new(AtomicBool::new(false));
-        Self { tx, rx, stopped }
+        Self {
+            tx,
+            rx,
+            stopped,
+            idle_timeout,
+        }
     }
 
     /// Get a copy of the channel to send messages to the action loop.
@@ -46,9 +89,19 @@ impl ActionLoop {
     /// Stops running once all writers drop their sender.
     /// returns an exit code to use.
     pub fn run(self) -> ExitAction {
-        let Self { tx, rx, stopped } = self;
+        let Self {
+            tx,
+            rx,
+            stopped,
+            idle_timeout,
+        } = self;
 
         let mut exit_action = ExitAction::CleanExit(0);
+        let mut exit_received = false;
+        // Set when we have asked the app to stop so it closing is expected.
+        let mut stop_requested = false;
+        let mut last_message = Instant::now();
+        let mut recent_output = RecentOutput::default();
 
         //Force drop our own unused sender.
         drop(tx);
@@ -56,27 +109,85 @@ impl ActionLoop {
         //this will run until there are no senders.
         // In here we set the stop signal to notify senders to stop.
         // Then this will stop automatically once they are all gone allowing us to process further messages.
-        for message in rx {
+        loop {
+            let message = match idle_timeout {
+                // Once stopping we just wait for the senders to finish.
+                Some(timeout) if !stopped.load(Ordering::Relaxed) => {
+                    match rx.recv_timeout(timeout.saturating_sub(last_message.elapsed())) {
+                        Ok(message) => message,
+                        Err(RecvTimeoutError::Timeout) => {
+                            set_stop(&stopped);
+                            report_idle(last_message.elapsed(), &recent_output);
+                            exit_action = ExitAction::IdleTimeout;
+                            stop_requested = true;
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                _ => match rx.recv() {
+                    Ok(message) => message,
+                    Err(_) => break,
+                },
+            };
+
+            if matches!(message, ActionMessage::LVMessage(_)) {
+                last_message = Instant::now();
+            }
+
             match message {
                 ActionMessage::LVMessage(MessageFromLV::OUTP(string)) => {
                     print!("{}", string);
+                    recent_output.push(&string);
                 }
                 ActionMessage::LVMessage(MessageFromLV::SERR(string)) => {
                     eprint!("{}", string);
+                    recent_output.push(&string);
                 }
                 ActionMessage::LVMessage(MessageFromLV::EXIT(code)) => {
                     exit_action = ExitAction::CleanExit(code);
+                    exit_received = true;
                     set_stop(&stopped);
                 }
                 ActionMessage::CommsError(e) => {
-                    exit_action = ExitAction::CleanExit(-1);
+                    // A dead process will also close the connection. Keep that as the cause.
+                    if !matches!(exit_action, ExitAction::AppTerminated) {
+                        exit_action = ExitAction::CommsFailed;
+                    }
                     set_stop(&stopped);
                     error!("Comms Error: {}", e);
                 }
+                ActionMessage::ProcessExited => {
+                    // Apps are free to close once they have sent their exit code.
+                    if !exit_received && !stop_requested {
+                        exit_action = ExitAction::AppTerminated;
+                        set_stop(&stopped);
+                        debug!("Application process exited before sending an exit code");
+                    }
+                }
                 ActionMessage::CtrlC => {
                     set_stop(&stopped);
                     debug!("Recieved Ctrl+C Kill Signal");
-                    exit_action = ExitAction::ForcedExit;
+                    exit_action = ExitAction::ForcedExit(SIGINT);
+                    stop_requested = true;
+                }
+                ActionMessage::Signal(signal, action) => {
+                    set_stop(&stopped);
+                    debug!("Recieved Signal {}. Action: {:?}", signal, action);
+                    // A later kill always wins so a second signal can escalate a cancel.
+                    if !matches!(exit_action, ExitAction::ForcedExit(_)) {
+                        exit_action = ExitAction::for_signal(signal, action);
+                    }
+                    stop_requested = true;
+                }
+                ActionMessage::RunTimeout => {
+                    // Ignore a timeout which races with the run finishing.
+                    if !stopped.load(Ordering::Relaxed) {
+                        set_stop(&stopped);
+                        debug!("Run timeout expired");
+                        exit_action = ExitAction::TimedOut;
+                        stop_requested = true;
+                    }
                 }
             }
         }
@@ -86,7 +197,80 @@ impl ActionLoop {
     }
 }
 
+/// Tells the user the app has gone quiet and what it last said.
+fn report_idle(silent_for: Duration, recent_output: &RecentOutput) {
+    error!(
+        "No messages from the application for {:?}. Treating the run as hung",
+        silent_for
+    );
+    let lines: Vec<&str> = recent_output.lines().collect();
+    if !lines.is_empty() {
+        eprintln!("Last output from the application:\n{}", lines.join("\n"));
+    }
+}
+
+/// The last few lines of output from the app.
+#[derive(Default)]
+struct RecentOutput {
+    lines: VecDeque<String>,
+    /// Output since the last newline.
+    current: String,
+}
+
+impl RecentOutput {
+    fn push(&mut self, output: &str) {
+        for chunk in output.split_inclusive('\n') {
+            self.current.push_str(chunk);
+            if self.current.ends_with('\n') {
+                let line = self.current.trim_end_matches(['\r', '\n']).to_owned();
+                self.current.clear();
+                self.lines.push_back(line);
+                if self.lines.len() > IDLE_CONTEXT_LINES {
+                    self.lines.pop_front();
+                }
+            }
+        }
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .chain(Some(self.current.as_str()).filter(|line| !line.is_empty()))
+    }
+}
+
 //helper function to simplify multiple calls.
 fn set_stop(stopped: &Arc<AtomicBool>) {
-    stopped.store(true, std::sync::atomic::Ordering::Relaxed)
+    stopped.store(true, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_output_joins_partial_lines() {
+        let mut output = RecentOutput::default();
+        output.push("Running test");
+        output.push(" 1\nRunning test 2\n");
+        output.push("Done\r\nWaiting");
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            vec!["Running test 1", "Running test 2", "Done", "Waiting"]
+        );
+    }
+
+    #[test]
+    fn recent_output_keeps_last_lines() {
+        let mut output = RecentOutput::default();
+        for line in 0..20 {
+            output.push(&format!("{line}\n"));
+        }
+
+        assert_eq!(output.lines.len(), IDLE_CONTEXT_LINES);
+        assert_eq!(output.lines.front().unwrap(), "10");
+        assert_eq!(output.lines.back().unwrap(), "19");
+    }
 }
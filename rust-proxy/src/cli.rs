@@ -6,7 +6,13 @@ use std::iter::IntoIterator;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::error_report::ErrorFormat;
+use crate::exit_codes::{self, ExitCodeMap, ExitCodes, OverflowPolicy};
+use crate::labview::environment::{self, EnvironmentOptions};
 use crate::labview::installs::Bitness;
+use crate::labview::preferences::{self, PreferenceOptions};
+use crate::labview::process::TerminationPolicy;
+use crate::signal_loop::{SignalAction, SignalActions};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -16,12 +22,58 @@ pub struct Configuration {
     pub lv_version_string: Option<String>,
     pub bitness: Bitness,
     pub connect_timeout: Duration,
+    /// If Some, the longest the run can last once the application has connected.
+    pub run_timeout: Option<Duration>,
+    /// If Some, the longest the application can go without sending output once connected.
+    pub idle_timeout: Option<Duration>,
+    /// How many times to retry launching and connecting to the application.
+    pub retries: u32,
+    /// The delay before the first retry. This doubles for each retry after.
+    pub retry_delay: Duration,
     /// If kill is Some then the value is a timeout to kill LabVIEW if it isn't already killed.
     pub kill: Option<Duration>,
+    /// How LabVIEW is terminated when it is killed on exit or after Ctrl+C.
+    pub kill_policy: TerminationPolicy,
+    /// Allow killing a LabVIEW instance that was running before we launched it.
+    pub kill_existing: bool,
     /// allows LabVIEW to show dialogs by removing the unattended flag.
     pub allow_dialogs: bool,
     /// Dont launch anything if this is true.
     pub no_launch: bool,
+    /// File to capture the standard output and error of the launched application into.
+    pub app_log: Option<PathBuf>,
+    /// Print resource usage statistics for the application at the end of the run.
+    pub stats: bool,
+    /// Changes to the environment of the launched application from the command line.
+    pub environment: EnvironmentOptions,
+    /// Files of environment variables to set, applied before the command line variables.
+    pub env_files: Vec<PathBuf>,
+    /// Working directory for the launched application. This doesn't change the CCWD reported to it.
+    pub app_workdir: Option<PathBuf>,
+    /// Generate a LabVIEW preference file for this run if Some.
+    pub lv_preferences: Option<PreferenceOptions>,
+    /// Give the run private temp, data and preference directories.
+    pub isolate: bool,
+    /// Extra arguments for LabVIEW or the executable before the `--` separator.
+    pub app_args: Vec<OsString>,
+    /// Command to prefix the launch with.
+    pub launcher: Option<Vec<OsString>>,
+    /// Don't start a virtual display when there is no display on Linux.
+    pub no_virtual_display: bool,
+    /// Kill the application if G-CLI dies and clean up after previous runs that did.
+    pub die_with_parent: bool,
+    /// What to do when SIGTERM, SIGHUP or SIGQUIT is received.
+    pub signal_actions: SignalActions,
+    /// How long LabVIEW has to exit after SIGTERM before it is killed, e.g. when a signal cancels the run.
+    pub kill_grace: Duration,
+    /// The exit codes for failures in G-CLI rather than the application.
+    pub exit_codes: ExitCodes,
+    /// Translation of the exit code sent by the application. The files are added before the command line mappings.
+    pub exit_code_map: ExitCodeMap,
+    /// Files of exit code mappings.
+    pub exit_code_map_files: Vec<PathBuf>,
+    /// How failures in G-CLI are reported.
+    pub error_format: ErrorFormat,
 }
 
 impl Configuration {
@@ -66,6 +118,14 @@ impl Configuration {
 
             // First cant panic due to default values. Second could panic if invalid.
             connect_timeout: Duration::from_millis(*args.get_one::<u64>("timeout (ms)").unwrap()),
+            run_timeout: args
+                .get_one::<u64>("run timeout (ms)")
+                .map(|timeout| Duration::from_millis(*timeout)),
+            idle_timeout: args
+                .get_one::<u64>("idle timeout (ms)")
+                .map(|timeout| Duration::from_millis(*timeout)),
+            retries: *args.get_one::<u32>("retries").unwrap(),
+            retry_delay: Duration::from_millis(*args.get_one::<u64>("retry delay (ms)").unwrap()),
             kill: if args.get_flag("kill") {
                 //todo handle unwraps. The first should not fail due to default. The second could.
                 Some(Duration::from_millis(
@@ -74,8 +134,90 @@ impl Configuration {
             } else {
                 None
             },
+            kill_policy: match args.get_one::<String>("kill policy").map(|s| s.as_str()) {
+                Some("graceful") => TerminationPolicy::Graceful(Duration::from_millis(
+                    *args.get_one::<u64>("kill grace (ms)").unwrap(),
+                )),
+                _ => TerminationPolicy::Immediate,
+            },
+            kill_existing: args.get_flag("kill existing"),
             allow_dialogs: args.get_flag("allow dialogs"),
             no_launch: args.get_flag("no launch"),
+            app_log: args.get_one::<PathBuf>("app log").cloned(),
+            stats: args.get_flag("stats"),
+            environment: EnvironmentOptions {
+                clear: args.get_flag("clear env"),
+                unset: args
+                    .get_many::<String>("unset env")
+                    .map(|keys| keys.map(OsString::from).collect())
+                    .unwrap_or_default(),
+                set: args
+                    .get_many::<(OsString, OsString)>("env")
+                    .map(|vars| vars.cloned().collect())
+                    .unwrap_or_default(),
+            },
+            exit_code_map: ExitCodeMap {
+                codes: args
+                    .get_many::<(i32, i32)>("exit code map")
+                    .map(|codes| codes.cloned().collect())
+                    .unwrap_or_default(),
+                overflow: match args
+                    .get_one::<String>("exit code overflow")
+                    .map(|s| s.as_str())
+                {
+                    Some("clamp") => OverflowPolicy::Clamp,
+                    Some("nonzero") => OverflowPolicy::NonZero,
+                    _ => OverflowPolicy::Truncate,
+                },
+            },
+            exit_code_map_files: args
+                .get_many::<PathBuf>("exit code map file")
+                .map(|files| files.cloned().collect())
+                .unwrap_or_default(),
+            error_format: match args.get_one::<String>("error format").map(|s| s.as_str()) {
+                Some("json") => ErrorFormat::Json,
+                _ => ErrorFormat::Human,
+            },
+            env_files: args
+                .get_many::<PathBuf>("env file")
+                .map(|files| files.cloned().collect())
+                .unwrap_or_default(),
+            app_workdir: args.get_one::<PathBuf>("app workdir").cloned(),
+            lv_preferences: if args.contains_id("lv ini") || args.contains_id("lv ini base") {
+                Some(PreferenceOptions {
+                    base: args.get_one::<PathBuf>("lv ini base").cloned(),
+                    overrides: args
+                        .get_many::<(String, String)>("lv ini")
+                        .map(|prefs| prefs.cloned().collect())
+                        .unwrap_or_default(),
+                    directory: None,
+                })
+            } else {
+                None
+            },
+            isolate: args.get_flag("isolate"),
+            app_args: args
+                .get_many::<String>("app arg")
+                .map(|app_args| app_args.map(OsString::from).collect())
+                .unwrap_or_default(),
+            launcher: args
+                .get_one::<Vec<String>>("launcher")
+                .map(|launcher| launcher.iter().map(OsString::from).collect()),
+            no_virtual_display: args.get_flag("no virtual display"),
+            die_with_parent: args.get_flag("die with parent"),
+            signal_actions: SignalActions {
+                terminate: signal_action(&args, "on sigterm"),
+                hangup: signal_action(&args, "on sighup"),
+                quit: signal_action(&args, "on sigquit"),
+            },
+            kill_grace: Duration::from_millis(*args.get_one::<u64>("kill grace (ms)").unwrap()),
+            exit_codes: ExitCodes {
+                offset: *args.get_one::<i32>("proxy exit code offset").unwrap(),
+                overrides: args
+                    .get_many::<(exit_codes::ProxyFailure, i32)>("proxy exit code")
+                    .map(|codes| codes.cloned().collect())
+                    .unwrap_or_default(),
+            },
         }
     }
 }
@@ -117,6 +259,32 @@ fn clap_app() -> clap::Command {
                 .value_parser(value_parser!(u64))
                 .default_value("60000"),
         )
+        .arg(
+            Arg::new("run timeout (ms)")
+                .long("run-timeout")
+                .help("The longest the run can last once the application has connected. When it expires LabVIEW is sent SIGTERM and, if it hasn't closed after kill-grace, killed according to kill-policy.")
+                .value_parser(value_parser!(u64))
+        )
+        .arg(
+            Arg::new("idle timeout (ms)")
+                .long("idle-timeout")
+                .help("Treat the run as hung if the application sends nothing for this long once it has connected. The last output is printed and LabVIEW is stopped as for --run-timeout.")
+                .value_parser(value_parser!(u64))
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .help("Retry launching LabVIEW or the executable this many times if it fails to launch or connect. Any process from the failed attempt is killed first.")
+                .value_parser(value_parser!(u32))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("retry delay (ms)")
+                .long("retry-delay")
+                .help("The delay before the first retry. This doubles for each retry after.")
+                .value_parser(value_parser!(u64))
+                .default_value("2000")
+        )
         .arg(
             Arg::new("kill")
             .long("kill")
@@ -130,6 +298,26 @@ fn clap_app() -> clap::Command {
                 .value_parser(value_parser!(u64))
                 .default_value("10000")
         )
+        .arg(
+            Arg::new("kill policy")
+                .long("kill-policy")
+                .value_parser(["immediate", "graceful"])
+                .default_value("immediate")
+                .help("How LabVIEW is killed with --kill or Ctrl+C. \"graceful\" sends SIGTERM and waits --kill-grace before sending SIGKILL. Windows always kills immediately.")
+        )
+        .arg(
+            Arg::new("kill existing")
+                .long("kill-existing")
+                .action(ArgAction::SetTrue)
+                .help("Allow --kill and Ctrl+C to kill a LabVIEW instance that was already running before G-CLI launched it. By default only processes G-CLI started are killed.")
+        )
+        .arg(
+            Arg::new("kill grace (ms)")
+                .long("kill-grace")
+                .help("The time LabVIEW has to exit after SIGTERM with the graceful kill policy or when a signal cancels the run.")
+                .value_parser(value_parser!(u64))
+                .default_value("5000")
+        )
         .arg(
             Arg::new("allow dialogs")
             .long("allow-dialogs")
@@ -143,10 +331,185 @@ fn clap_app() -> clap::Command {
                 .action(ArgAction::SetTrue)
             .help("Don't launch your VI or application automatically. You must start it manually.")
         )
+        .arg(
+            Arg::new("app log")
+                .long("app-log")
+                .value_parser(value_parser!(PathBuf))
+                .help("Capture the standard output and error of the launched LabVIEW process or executable into this file. These are printed if the app fails to connect or in verbose mode. Linux only.")
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .action(ArgAction::SetTrue)
+                .help("Print the peak memory, CPU time and wall-clock time of the launched process to standard error at the end of the run.")
+        )
+        .arg(
+            Arg::new("env")
+                .long("env")
+                .action(ArgAction::Append)
+                .value_parser(environment::parse_variable)
+                .help("Set an environment variable for the launched process as KEY=VALUE. Can be used multiple times.")
+        )
+        .arg(
+            Arg::new("env file")
+                .long("env-file")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf))
+                .help("Set environment variables for the launched process from a file of KEY=VALUE lines. Variables from --env override these.")
+        )
+        .arg(
+            Arg::new("unset env")
+                .long("unset-env")
+                .action(ArgAction::Append)
+                .help("Remove an environment variable from the launched process. Can be used multiple times.")
+        )
+        .arg(
+            Arg::new("clear env")
+                .long("clear-env")
+                .action(ArgAction::SetTrue)
+                .help("Launch the process with only the variables set by --env and --env-file instead of inheriting the environment.")
+        )
+        .arg(
+            Arg::new("app workdir")
+                .long("app-workdir")
+                .value_parser(value_parser!(PathBuf))
+                .help("Set the working directory of the launched process. The current directory reported to the VI is still the one G-CLI is run from.")
+        )
+        .arg(
+            Arg::new("lv ini")
+                .long("lv-ini")
+                .action(ArgAction::Append)
+                .value_parser(preferences::parse_preference)
+                .help("Set a LabVIEW preference for this run as key=value, e.g. server.tcp.enabled=False. LabVIEW is launched with a temporary preference file so the machine-wide LabVIEW.ini isn't changed. Can be used multiple times.")
+        )
+        .arg(
+            Arg::new("lv ini base")
+                .long("lv-ini-base")
+                .value_parser(value_parser!(PathBuf))
+                .help("The preference file to copy into the temporary preference file. Defaults to the LabVIEW.ini of the install on Windows.")
+        )
+        .arg(
+            Arg::new("isolate")
+                .long("isolate")
+                .action(ArgAction::SetTrue)
+                .help("Give the launched process private temp, data and preference directories which are removed on exit. Allows parallel runs on the same machine without sharing caches and recovery files.")
+        )
+        .arg(
+            Arg::new("app arg")
+                .long("app-arg")
+                .action(ArgAction::Append)
+                .allow_hyphen_values(true)
+                .help("Pass an extra argument to LabVIEW or the executable, e.g. --app-arg=-nosplash. These are placed before the arguments for your VI. Can be used multiple times.")
+        )
+        .arg(
+            Arg::new("launcher")
+                .long("launcher")
+                .env("G_CLI_LAUNCHER")
+                .value_parser(parse_launcher)
+                .help("Run LabVIEW or the executable through this command, e.g. --launcher \"xvfb-run -a\". Arguments are split like a shell and G-CLI monitors the LabVIEW process the launcher starts.")
+        )
+        .arg(
+            Arg::new("no virtual display")
+                .long("no-virtual-display")
+                .action(ArgAction::SetTrue)
+                .help("Linux only. Don't start a private Xvfb display when DISPLAY isn't set.")
+        )
+        .arg(
+            Arg::new("die with parent")
+                .long("die-with-parent")
+                .action(ArgAction::SetTrue)
+                .help("Linux only. Kill LabVIEW and everything it started if G-CLI is killed, and clean up anything left behind by previous runs that were. LabVIEW will always close when G-CLI exits.")
+        )
+        .arg(
+            Arg::new("on sigterm")
+                .long("on-sigterm")
+                .value_parser(["cancel", "kill", "detach"])
+                .default_value("kill")
+                .help("Unix only. What to do when G-CLI receives SIGTERM. \"cancel\" sends LabVIEW SIGTERM and kills it after kill-grace, \"kill\" kills it like Ctrl+C and \"detach\" leaves it running.")
+        )
+        .arg(
+            Arg::new("on sighup")
+                .long("on-sighup")
+                .value_parser(["cancel", "kill", "detach"])
+                .default_value("kill")
+                .help("Unix only. What to do when G-CLI receives SIGHUP. \"cancel\" sends LabVIEW SIGTERM and kills it after kill-grace, \"kill\" kills it like Ctrl+C and \"detach\" leaves it running.")
+        )
+        .arg(
+            Arg::new("on sigquit")
+                .long("on-sigquit")
+                .value_parser(["cancel", "kill", "detach"])
+                .default_value("kill")
+                .help("Unix only. What to do when G-CLI receives SIGQUIT. \"cancel\" sends LabVIEW SIGTERM and kills it after kill-grace, \"kill\" kills it like Ctrl+C and \"detach\" leaves it running.")
+        )
+        .arg(
+            Arg::new("proxy exit code")
+                .long("proxy-exit-code")
+                .action(ArgAction::Append)
+                .value_parser(exit_codes::parse_override)
+                .allow_hyphen_values(true)
+                .help("Set the exit code for a G-CLI failure as name=code, e.g. connect-timeout=1. Names are internal, launch-failed, install-not-found, connect-timeout, comms-error, app-terminated, run-timeout, idle-timeout, sighup, sigint, sigquit and sigterm. Can be repeated.")
+        )
+        .arg(
+            Arg::new("proxy exit code offset")
+                .long("proxy-exit-code-offset")
+                .value_parser(value_parser!(i32))
+                .allow_hyphen_values(true)
+                .default_value("0")
+                .help("Shift the exit codes for G-CLI failures (from 110 by default) by this amount to avoid codes your application uses. Signal codes are not shifted.")
+        )
+        .arg(
+            Arg::new("exit code map")
+                .long("exit-code-map")
+                .action(ArgAction::Append)
+                .value_parser(exit_codes::parse_mapping)
+                .allow_hyphen_values(true)
+                .help("Replace an exit code from the application as from=to, e.g. -1073807346=3. Can be repeated.")
+        )
+        .arg(
+            Arg::new("exit code map file")
+                .long("exit-code-map-file")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf))
+                .help("File of from=to exit code mappings, one per line. --exit-code-map entries override these.")
+        )
+        .arg(
+            Arg::new("exit code overflow")
+                .long("exit-code-overflow")
+                .value_parser(["truncate", "clamp", "nonzero"])
+                .default_value("truncate")
+                .help("What to do with an exit code outside 0-255 after mapping on Linux. \"truncate\" keeps the low 8 bits, \"clamp\" limits it to 1-255 and \"nonzero\" exits with 1 and prints the original.")
+        )
+        .arg(
+            Arg::new("error format")
+                .long("error-format")
+                .value_parser(["human", "json"])
+                .default_value("human")
+                .help("How failures in G-CLI are written to stderr. \"json\" writes a single line JSON object with the failure, exit code, error kind, message, causes and context.")
+        )
         .trailing_var_arg(true)
         .arg(Arg::new("app to run").action(ArgAction::Append).required(true))
 }
 
+/// Reads the action for a signal. Can't fail due to the default value.
+fn signal_action(args: &ArgMatches, id: &str) -> SignalAction {
+    match args.get_one::<String>(id).map(|s| s.as_str()) {
+        Some("cancel") => SignalAction::Cancel,
+        Some("detach") => SignalAction::Detach,
+        _ => SignalAction::Kill,
+    }
+}
+
+/// Splits the launcher command like a shell would.
+fn parse_launcher(launcher: &str) -> Result<Vec<String>, String> {
+    match shlex::split(launcher) {
+        Some(parts) if !parts.is_empty() => Ok(parts),
+        Some(_) => Err(String::from("The launcher command is empty")),
+        None => Err(format!(
+            "Unable to parse the launcher command \"{launcher}\""
+        )),
+    }
+}
+
 /// Extract the arguments that are going to be passed to the VI/exe we will run.
 pub fn program_arguments<T: IntoIterator<Item = OsString>>(main_args: T) -> Vec<OsString> {
     let args_iter = main_args.into_iter();
@@ -479,6 +842,662 @@ mod tests {
         assert_eq!(None, config.kill);
     }
 
+    #[test]
+    fn kill_policy_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--kill"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(TerminationPolicy::Immediate, config.kill_policy);
+    }
+
+    #[test]
+    fn kill_policy_graceful_default_grace() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--kill-policy"),
+            String::from("graceful"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            TerminationPolicy::Graceful(Duration::from_secs(5)),
+            config.kill_policy
+        );
+    }
+
+    #[test]
+    fn kill_policy_graceful_with_grace() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--kill-policy"),
+            String::from("graceful"),
+            String::from("--kill-grace"),
+            String::from("2000"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            TerminationPolicy::Graceful(Duration::from_millis(2000)),
+            config.kill_policy
+        );
+    }
+
+    #[test]
+    fn environment_defaults_to_inherited() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.environment.is_inherited());
+        assert!(config.env_files.is_empty());
+    }
+
+    #[test]
+    fn environment_options() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--env"),
+            String::from("LD_LIBRARY_PATH=/opt/lib"),
+            String::from("--env"),
+            String::from("EMPTY="),
+            String::from("--unset-env"),
+            String::from("DISPLAY"),
+            String::from("--env-file"),
+            String::from("ci.env"),
+            String::from("--clear-env"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            config.environment,
+            EnvironmentOptions {
+                clear: true,
+                unset: vec![OsString::from("DISPLAY")],
+                set: vec![
+                    (
+                        OsString::from("LD_LIBRARY_PATH"),
+                        OsString::from("/opt/lib")
+                    ),
+                    (OsString::from("EMPTY"), OsString::new()),
+                ],
+            }
+        );
+        assert_eq!(config.env_files, vec![PathBuf::from("ci.env")]);
+    }
+
+    #[test]
+    fn env_without_value_is_rejected() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--env"),
+            String::from("NOVALUE"),
+            String::from("test.vi"),
+        ];
+
+        assert!(clap_app().try_get_matches_from(args).is_err());
+    }
+
+    #[test]
+    fn app_workdir_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(None, config.app_workdir);
+    }
+
+    #[test]
+    fn app_workdir_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--app-workdir"),
+            String::from("/opt/app"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(Some(PathBuf::from("/opt/app")), config.app_workdir);
+    }
+
+    #[test]
+    fn lv_preferences_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(None, config.lv_preferences);
+    }
+
+    #[test]
+    fn lv_preferences_overrides() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--lv-ini"),
+            String::from("server.tcp.enabled=False"),
+            String::from("--lv-ini"),
+            String::from("AutoErrorHandling=False"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            Some(PreferenceOptions {
+                base: None,
+                overrides: vec![
+                    (String::from("server.tcp.enabled"), String::from("False")),
+                    (String::from("AutoErrorHandling"), String::from("False")),
+                ],
+                directory: None,
+            }),
+            config.lv_preferences
+        );
+    }
+
+    #[test]
+    fn lv_preferences_base_only() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--lv-ini-base"),
+            String::from("ci.ini"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            Some(PreferenceOptions {
+                base: Some(PathBuf::from("ci.ini")),
+                overrides: Vec::new(),
+                directory: None,
+            }),
+            config.lv_preferences
+        );
+    }
+
+    #[test]
+    fn isolate_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(!config.isolate);
+    }
+
+    #[test]
+    fn isolate_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--isolate"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.isolate);
+    }
+
+    #[test]
+    fn app_args() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--app-arg"),
+            String::from("-nosplash"),
+            String::from("--app-arg=-pref"),
+            String::from("--app-arg"),
+            String::from("custom.ini"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            vec![
+                OsString::from("-nosplash"),
+                OsString::from("-pref"),
+                OsString::from("custom.ini")
+            ],
+            config.app_args
+        );
+        assert_eq!(PathBuf::from("test.vi"), config.to_launch);
+    }
+
+    #[test]
+    fn launcher_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(None, config.launcher);
+    }
+
+    #[test]
+    fn launcher_is_split() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--launcher"),
+            String::from("xvfb-run -a --server-args='-screen 0 1024x768x24'"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            Some(vec![
+                OsString::from("xvfb-run"),
+                OsString::from("-a"),
+                OsString::from("--server-args=-screen 0 1024x768x24"),
+            ]),
+            config.launcher
+        );
+    }
+
+    #[test]
+    fn launcher_unbalanced_quotes_rejected() {
+        assert!(parse_launcher("nice \"-n").is_err());
+        assert!(parse_launcher("  ").is_err());
+    }
+
+    #[test]
+    fn virtual_display_enabled_by_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(!config.no_virtual_display);
+    }
+
+    #[test]
+    fn no_virtual_display() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--no-virtual-display"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.no_virtual_display);
+    }
+
+    #[test]
+    fn die_with_parent_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(!config.die_with_parent);
+    }
+
+    #[test]
+    fn die_with_parent_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--die-with-parent"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.die_with_parent);
+    }
+
+    #[test]
+    fn signal_actions_default_to_kill() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(SignalActions::default(), config.signal_actions);
+    }
+
+    #[test]
+    fn signal_actions_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--on-sigterm"),
+            String::from("cancel"),
+            String::from("--on-sighup"),
+            String::from("detach"),
+            String::from("--kill-grace"),
+            String::from("3000"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            SignalActions {
+                terminate: SignalAction::Cancel,
+                hangup: SignalAction::Detach,
+                quit: SignalAction::Kill,
+            },
+            config.signal_actions
+        );
+        assert_eq!(Duration::from_millis(3000), config.kill_grace);
+    }
+
+    #[test]
+    fn proxy_exit_codes_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(ExitCodes::default(), config.exit_codes);
+    }
+
+    #[test]
+    fn proxy_exit_codes_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--proxy-exit-code"),
+            String::from("app-terminated=-2"),
+            String::from("--proxy-exit-code-offset"),
+            String::from("-100"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            ExitCodes {
+                offset: -100,
+                overrides: vec![(exit_codes::ProxyFailure::AppTerminated, -2)],
+            },
+            config.exit_codes
+        );
+    }
+
+    #[test]
+    fn exit_code_map_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(ExitCodeMap::default(), config.exit_code_map);
+        assert!(config.exit_code_map_files.is_empty());
+    }
+
+    #[test]
+    fn exit_code_map_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--exit-code-map"),
+            String::from("-1073807346=3"),
+            String::from("--exit-code-map-file"),
+            String::from("codes.txt"),
+            String::from("--exit-code-overflow"),
+            String::from("nonzero"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(
+            ExitCodeMap {
+                codes: vec![(-1073807346, 3)],
+                overflow: OverflowPolicy::NonZero,
+            },
+            config.exit_code_map
+        );
+        assert_eq!(vec![PathBuf::from("codes.txt")], config.exit_code_map_files);
+    }
+
+    #[test]
+    fn run_timeout_not_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(None, config.run_timeout);
+    }
+
+    #[test]
+    fn run_timeout_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--run-timeout"),
+            String::from("600000"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(Some(Duration::from_millis(600_000)), config.run_timeout);
+    }
+
+    #[test]
+    fn idle_timeout_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--idle-timeout"),
+            String::from("30000"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(Some(Duration::from_millis(30_000)), config.idle_timeout);
+        assert_eq!(None, config.run_timeout);
+    }
+
+    #[test]
+    fn retries_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(0, config.retries);
+        assert_eq!(Duration::from_millis(2000), config.retry_delay);
+    }
+
+    #[test]
+    fn retries_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--retries"),
+            String::from("3"),
+            String::from("--retry-delay"),
+            String::from("500"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(3, config.retries);
+        assert_eq!(Duration::from_millis(500), config.retry_delay);
+    }
+
+    #[test]
+    fn error_format_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(ErrorFormat::Human, config.error_format);
+    }
+
+    #[test]
+    fn error_format_json() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--error-format"),
+            String::from("json"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(ErrorFormat::Json, config.error_format);
+    }
+
+    #[test]
+    fn kill_existing_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--kill"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(!config.kill_existing);
+    }
+
+    #[test]
+    fn kill_existing_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--kill"),
+            String::from("--kill-existing"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.kill_existing);
+    }
+
+    #[test]
+    fn stats_default() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(!config.stats);
+    }
+
+    #[test]
+    fn stats_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--stats"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert!(config.stats);
+    }
+
+    #[test]
+    fn app_log_not_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(None, config.app_log);
+    }
+
+    #[test]
+    fn app_log_set() {
+        let args = vec![
+            String::from("g-cli"),
+            String::from("--app-log"),
+            String::from("lv.log"),
+            String::from("test.vi"),
+            String::from("--"),
+            String::from("test1"),
+        ];
+
+        let config = Configuration::from_arg_array(args);
+        assert_eq!(Some(PathBuf::from("lv.log")), config.app_log);
+    }
+
     #[test]
     fn get_program_arguments() {
         let args = vec![
@@ -37,12 +37,38 @@ pub enum CommsError {
     WriteLvMessageError(#[source] std::io::Error),
     #[error("Timed out waiting for app to connect to g-cli (Timeout: {0:?})")]
     WaitOnConnectionTimeOut(std::time::Duration),
+    #[error(
+        "Stopped waiting for app to connect to g-cli as the application terminated unexpectedly"
+    )]
+    WaitOnConnectionAborted,
     #[error("System error setting up app connection")]
     ErrorCreatingConnection(#[source] std::io::Error),
     #[error("System error setting up app listener")]
     ErrorCreatingListener(#[source] std::io::Error),
 }
 
+impl CommsError {
+    /// The name of the variant, e.g. for scripts to match on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SizeParameterInvalid => "SizeParameterInvalid",
+            Self::MessageIdNotValidUTF8(..) => "MessageIdNotValidUTF8",
+            Self::MessageContentsNotValidUTF8(..) => "MessageContentsNotValidUTF8",
+            Self::ExitCodeStringNotParsable(..) => "ExitCodeStringNotParsable",
+            Self::UnknownMessageId(..) => "UnknownMessageId",
+            Self::ConnectionClosedEof(..) => "ConnectionClosedEof",
+            Self::ConenctionClosedAborted(..) => "ConenctionClosedAborted",
+            Self::WaitOnConnectionIoError(..) => "WaitOnConnectionIoError",
+            Self::ReadLvMessageError(..) => "ReadLvMessageError",
+            Self::WriteLvMessageError(..) => "WriteLvMessageError",
+            Self::WaitOnConnectionTimeOut(..) => "WaitOnConnectionTimeOut",
+            Self::WaitOnConnectionAborted => "WaitOnConnectionAborted",
+            Self::ErrorCreatingConnection(..) => "ErrorCreatingConnection",
+            Self::ErrorCreatingListener(..) => "ErrorCreatingListener",
+        }
+    }
+}
+
 /// Provides the TCP Connection to the App
 pub struct AppListener {
     listener: TcpListener,
@@ -63,7 +89,13 @@ impl AppListener {
     }
 
     /// Get a Connection
-    pub fn wait_on_app(&self, timeout: Duration) -> Result<AppConnection, CommsError> {
+    ///
+    /// `abort` is checked while waiting so we can give up early, for example if the app has exited or we receive a signal.
+    pub fn wait_on_app(
+        &self,
+        timeout: Duration,
+        mut abort: impl FnMut() -> bool,
+    ) -> Result<AppConnection, CommsError> {
         // The standard networking library doesn't contain a timeout based TCP listener.
         // There maybe better methods than polling but this is where we can start.
 
@@ -79,7 +111,9 @@ impl AppListener {
                 Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     count += 1;
 
-                    if count < iterations {
+                    if abort() {
+                        return Err(CommsError::WaitOnConnectionAborted);
+                    } else if count < iterations {
                         //retry
                         sleep(wait_time);
                     } else {
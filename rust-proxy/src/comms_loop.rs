@@ -40,8 +40,19 @@ pub fn start(mut connection: AppConnection, tx: Sender<ActionMessage>, stop: Arc
                         std::thread::sleep(EMPTY_PAUSE);
                     }
                     Err(error) => {
+                        // Nothing more will arrive once the connection is closed, for example if LabVIEW crashed.
+                        let connection_closed = matches!(
+                            error,
+                            CommsError::ConnectionClosedEof(_)
+                                | CommsError::ConenctionClosedAborted(_)
+                        );
+
                         tx.send(ActionMessage::CommsError(Box::new(error)))
                             .expect("Cant send to action loop.");
+
+                        if connection_closed {
+                            break;
+                        }
                     }
                 }
                 //check stop outside of match to prevent an stream of messages holding the program open.
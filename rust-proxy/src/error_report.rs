@@ -0,0 +1,181 @@
+//! Reports failures in G-CLI itself, either for people or as JSON for scripts.
+//!
+use eyre::Report;
+use serde_json::{json, Value};
+use std::error::Error;
+
+use crate::cli::Configuration;
+use crate::comms::CommsError;
+use crate::exit_codes::ProxyFailure;
+use crate::labview::detect_installations;
+use crate::labview::error::LabVIEWError;
+use crate::labview::installs::LabviewInstallError;
+use crate::labview::process::ProcessStats;
+
+/// How failures in G-CLI are written to stderr.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ErrorFormat {
+    /// The full error report with its causes.
+    #[default]
+    Human,
+    /// A JSON object on a single line.
+    Json,
+}
+
+/// Writes the failure to stderr in the configured format.
+/// The stats of the application are included in the JSON if it was launched.
+pub fn print(
+    config: &Configuration,
+    report: &Report,
+    failure: ProxyFailure,
+    code: i32,
+    stats: Option<&ProcessStats>,
+) {
+    match config.error_format {
+        ErrorFormat::Human => eprintln!("Error: {report:?}"),
+        ErrorFormat::Json => eprintln!("{}", json_report(config, report, failure, code, stats)),
+    }
+}
+
+fn json_report(
+    config: &Configuration,
+    report: &Report,
+    failure: ProxyFailure,
+    code: i32,
+    stats: Option<&ProcessStats>,
+) -> Value {
+    let mut context = json!({
+        "to_launch": config.to_launch.to_string_lossy(),
+        "requested_version": config.lv_version_string,
+        "bitness": config.bitness.to_string(),
+    });
+
+    // Tell them what we could have used.
+    if failure == ProxyFailure::InstallNotFound {
+        let installs: Vec<Value> = detect_installations()
+            .map(|system| {
+                system
+                    .installs()
+                    .map(|install| {
+                        json!({
+                            "version": install.version,
+                            "bitness": install.bitness.to_string(),
+                            "path": install.path.to_string_lossy(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        context["detected_installs"] = Value::from(installs);
+    }
+
+    json!({
+        "failure": failure.name(),
+        "exit_code": code,
+        "kind": report.chain().find_map(error_kind),
+        "message": report.to_string(),
+        "causes": report.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+        "context": context,
+        "stats": stats.map(|stats| json!({
+            "wall_time_s": stats.wall_time.as_secs_f64(),
+            "cpu_time_s": stats.cpu_time.map(|time| time.as_secs_f64()),
+            "peak_memory_bytes": stats.peak_memory,
+        })),
+    })
+}
+
+/// The type and variant of the error if it is one of ours, e.g. `CommsError::WaitOnConnectionTimeOut`.
+fn error_kind(error: &(dyn Error + 'static)) -> Option<String> {
+    if let Some(error) = error.downcast_ref::<CommsError>() {
+        Some(format!("CommsError::{}", error.kind()))
+    } else if let Some(error) = error.downcast_ref::<LabVIEWError>() {
+        Some(format!("LabVIEWError::{}", error.kind()))
+    } else {
+        error
+            .downcast_ref::<LabviewInstallError>()
+            .map(|error| format!("LabviewInstallError::{}", error.kind()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+    use std::time::Duration;
+
+    fn config() -> Configuration {
+        Configuration::from_arg_array(vec![
+            String::from("g-cli"),
+            String::from("--lv-ver"),
+            String::from("2020"),
+            String::from("test.vi"),
+        ])
+    }
+
+    #[test]
+    fn json_report_has_kind_and_causes() {
+        let error: Result<(), CommsError> =
+            Err(CommsError::WaitOnConnectionTimeOut(Duration::from_secs(1)));
+        let report = error.wrap_err("No connection").unwrap_err();
+
+        let json = json_report(&config(), &report, ProxyFailure::ConnectTimeout, 113, None);
+
+        assert_eq!(json["failure"], "connect-timeout");
+        assert_eq!(json["exit_code"], 113);
+        assert_eq!(json["kind"], "CommsError::WaitOnConnectionTimeOut");
+        assert_eq!(json["message"], "No connection");
+        assert_eq!(
+            json["causes"][0],
+            "Timed out waiting for app to connect to g-cli (Timeout: 1s)"
+        );
+        assert_eq!(json["context"]["requested_version"], "2020");
+        assert_eq!(json["context"]["to_launch"], "test.vi");
+    }
+
+    #[test]
+    fn json_report_without_error_type() {
+        let report = eyre::eyre!("The run timed out after 1s");
+
+        let json = json_report(&config(), &report, ProxyFailure::RunTimeout, 116, None);
+
+        assert_eq!(json["failure"], "run-timeout");
+        assert_eq!(json["kind"], Value::Null);
+        assert_eq!(json["message"], "The run timed out after 1s");
+        assert_eq!(json["stats"], Value::Null);
+    }
+
+    #[test]
+    fn json_report_with_stats() {
+        let report = eyre::eyre!("The application terminated unexpectedly");
+        let stats = ProcessStats {
+            peak_memory: Some(1024),
+            cpu_time: None,
+            wall_time: Duration::from_millis(1500),
+        };
+
+        let json = json_report(
+            &config(),
+            &report,
+            ProxyFailure::AppTerminated,
+            115,
+            Some(&stats),
+        );
+
+        assert_eq!(json["stats"]["wall_time_s"], 1.5);
+        assert_eq!(json["stats"]["cpu_time_s"], Value::Null);
+        assert_eq!(json["stats"]["peak_memory_bytes"], 1024);
+    }
+
+    #[test]
+    fn kind_of_unit_variant() {
+        let error = LabVIEWError::InstallNotFound;
+        assert_eq!(error_kind(&error).unwrap(), "LabVIEWError::InstallNotFound");
+        assert_eq!(error_kind(&std::fmt::Error), None);
+    }
+
+    #[test]
+    fn kind_of_variant_with_fields() {
+        let error = LabVIEWError::ViDoesNotExist("test.vi".into());
+        assert_eq!(error_kind(&error).unwrap(), "LabVIEWError::ViDoesNotExist");
+    }
+}
@@ -0,0 +1,388 @@
+//! The exit codes G-CLI uses for its own failures so they can be told apart from
+//! codes returned by the application.
+//!
+use eyre::{eyre, Report, WrapErr};
+use log::warn;
+use std::path::Path;
+
+use crate::comms::CommsError;
+use crate::labview::error::LabVIEWError;
+use crate::labview::installs::LabviewInstallError;
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGTERM: i32 = 15;
+
+/// A reason G-CLI exited without an exit code from the application.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProxyFailure {
+    /// A failure in G-CLI which doesn't fit another category.
+    Internal,
+    /// LabVIEW or the executable couldn't be launched.
+    LaunchFailed,
+    /// No LabVIEW install could be found to run the VI.
+    InstallNotFound,
+    /// The application didn't connect within the connect timeout.
+    ConnectTimeout,
+    /// The connection to the application failed.
+    CommsError,
+    /// The application exited without sending an exit code.
+    AppTerminated,
+    /// The run lasted longer than the run timeout.
+    RunTimeout,
+    /// The application sent nothing for the idle timeout.
+    IdleTimeout,
+    /// G-CLI was stopped by this signal. Ctrl+C is SIGINT on every platform.
+    Signal(i32),
+}
+
+impl ProxyFailure {
+    /// The code for the failure before any remapping.
+    ///
+    /// Signals follow the shell convention of 128 plus the signal number.
+    pub fn default_code(&self) -> i32 {
+        match self {
+            Self::Internal => 110,
+            Self::LaunchFailed => 111,
+            Self::InstallNotFound => 112,
+            Self::ConnectTimeout => 113,
+            Self::CommsError => 114,
+            Self::AppTerminated => 115,
+            Self::RunTimeout => 116,
+            Self::IdleTimeout => 117,
+            Self::Signal(signal) => 128 + signal,
+        }
+    }
+
+    /// The name used for the failure on the command line and in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Internal => "internal",
+            Self::LaunchFailed => "launch-failed",
+            Self::InstallNotFound => "install-not-found",
+            Self::ConnectTimeout => "connect-timeout",
+            Self::CommsError => "comms-error",
+            Self::AppTerminated => "app-terminated",
+            Self::RunTimeout => "run-timeout",
+            Self::IdleTimeout => "idle-timeout",
+            Self::Signal(SIGHUP) => "sighup",
+            Self::Signal(SIGINT) => "sigint",
+            Self::Signal(SIGQUIT) => "sigquit",
+            Self::Signal(SIGTERM) => "sigterm",
+            Self::Signal(_) => "signal",
+        }
+    }
+
+    /// Parses the name used for the failure on the command line.
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Internal,
+            Self::LaunchFailed,
+            Self::InstallNotFound,
+            Self::ConnectTimeout,
+            Self::CommsError,
+            Self::AppTerminated,
+            Self::RunTimeout,
+            Self::IdleTimeout,
+            Self::Signal(SIGHUP),
+            Self::Signal(SIGINT),
+            Self::Signal(SIGQUIT),
+            Self::Signal(SIGTERM),
+        ]
+        .into_iter()
+        .find(|failure| failure.name() == name)
+    }
+
+    /// Works out which failure caused an error from the chain of sources.
+    pub fn classify(report: &Report) -> Self {
+        for cause in report.chain() {
+            if let Some(error) = cause.downcast_ref::<CommsError>() {
+                return match error {
+                    CommsError::WaitOnConnectionTimeOut(_) => Self::ConnectTimeout,
+                    CommsError::WaitOnConnectionAborted => Self::AppTerminated,
+                    _ => Self::CommsError,
+                };
+            }
+            if let Some(error) = cause.downcast_ref::<LabVIEWError>() {
+                return match error {
+                    LabVIEWError::InstallNotFound => Self::InstallNotFound,
+                    _ => Self::LaunchFailed,
+                };
+            }
+            // Detection fails when there is no install directory or registry key to scan.
+            if cause.is::<LabviewInstallError>() {
+                return Self::InstallNotFound;
+            }
+        }
+        Self::Internal
+    }
+}
+
+/// Translates failures to the exit code to report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExitCodes {
+    /// Added to the default code of every failure which isn't a signal.
+    pub offset: i32,
+    /// Codes to use instead of the default, later entries override earlier ones.
+    pub overrides: Vec<(ProxyFailure, i32)>,
+}
+
+impl ExitCodes {
+    pub fn code(&self, failure: ProxyFailure) -> i32 {
+        if let Some((_, code)) = self.overrides.iter().rev().find(|(f, _)| *f == failure) {
+            return *code;
+        }
+        match failure {
+            ProxyFailure::Signal(_) => failure.default_code(),
+            _ => failure.default_code().saturating_add(self.offset),
+        }
+    }
+}
+
+/// Parses a `name=code` exit code argument.
+pub fn parse_override(mapping: &str) -> Result<(ProxyFailure, i32), String> {
+    let (name, code) = mapping
+        .split_once('=')
+        .ok_or_else(|| format!("\"{mapping}\" is not a valid exit code. Expected name=code"))?;
+    let failure = ProxyFailure::from_name(name.trim())
+        .ok_or_else(|| format!("\"{}\" is not a G-CLI failure", name.trim()))?;
+    let code = code
+        .trim()
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a valid exit code", code.trim()))?;
+    Ok((failure, code))
+}
+
+/// What to do with an exit code from the application which doesn't fit in the 0-255
+/// the OS reports on Unix.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OverflowPolicy {
+    /// Leave it to the OS, which keeps the low 8 bits.
+    #[default]
+    Truncate,
+    /// Limit it to 1-255 so a failure is never reported as success.
+    Clamp,
+    /// Exit with 1 and print the original code.
+    NonZero,
+}
+
+/// Translates the exit code sent by the application.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExitCodeMap {
+    /// Codes to replace, later entries override earlier ones.
+    pub codes: Vec<(i32, i32)>,
+    /// Applied to the code after mapping.
+    pub overflow: OverflowPolicy,
+}
+
+impl ExitCodeMap {
+    /// Adds the mappings in a file to the codes. Each line is a `from=to` pair.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn load_file(&mut self, path: &Path) -> eyre::Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read the exit code map \"{}\"", path.display()))?;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mapping = parse_mapping(line).map_err(|e| {
+                eyre!(
+                    "Invalid line in the exit code map \"{}\" at line {}: {}",
+                    path.display(),
+                    number + 1,
+                    e
+                )
+            })?;
+            self.codes.push(mapping);
+        }
+        Ok(())
+    }
+
+    /// The code G-CLI should exit with for the code sent by the application.
+    pub fn translate(&self, code: i32) -> i32 {
+        // Windows reports the full 32 bit code.
+        self.translate_with_limit(code, cfg!(unix))
+    }
+
+    fn translate_with_limit(&self, code: i32, limited: bool) -> i32 {
+        let mapped = self
+            .codes
+            .iter()
+            .rev()
+            .find(|(from, _)| *from == code)
+            .map_or(code, |(_, to)| *to);
+
+        if !limited || (0..=255).contains(&mapped) {
+            return mapped;
+        }
+
+        match self.overflow {
+            OverflowPolicy::Truncate => {
+                if mapped & 0xFF == 0 {
+                    warn!(
+                        "Exit code {} will be reported as 0 as only the low 8 bits are kept",
+                        mapped
+                    );
+                }
+                mapped
+            }
+            OverflowPolicy::Clamp => mapped.clamp(1, 255),
+            OverflowPolicy::NonZero => {
+                warn!(
+                    "The application exited with code {} which is outside 0-255 so exiting with 1",
+                    mapped
+                );
+                1
+            }
+        }
+    }
+}
+
+/// Parses a `from=to` exit code mapping.
+pub fn parse_mapping(mapping: &str) -> Result<(i32, i32), String> {
+    let parse_code = |code: &str| {
+        code.trim()
+            .parse::<i32>()
+            .map_err(|_| format!("\"{}\" is not a valid exit code", code.trim()))
+    };
+    let (from, to) = mapping
+        .split_once('=')
+        .ok_or_else(|| format!("\"{mapping}\" is not a valid mapping. Expected from=to"))?;
+    Ok((parse_code(from)?, parse_code(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_use_shell_convention() {
+        assert_eq!(ProxyFailure::Signal(SIGINT).default_code(), 130);
+        assert_eq!(ProxyFailure::Signal(SIGTERM).default_code(), 143);
+    }
+
+    #[test]
+    fn offset_does_not_move_signals() {
+        let codes = ExitCodes {
+            offset: 100,
+            overrides: Vec::new(),
+        };
+
+        assert_eq!(codes.code(ProxyFailure::ConnectTimeout), 213);
+        assert_eq!(codes.code(ProxyFailure::Signal(SIGTERM)), 143);
+    }
+
+    #[test]
+    fn offset_saturates() {
+        let codes = ExitCodes {
+            offset: i32::MAX,
+            overrides: Vec::new(),
+        };
+
+        assert_eq!(codes.code(ProxyFailure::Internal), i32::MAX);
+    }
+
+    #[test]
+    fn override_beats_offset() {
+        let codes = ExitCodes {
+            offset: 100,
+            overrides: vec![
+                (ProxyFailure::AppTerminated, -2),
+                (ProxyFailure::Signal(SIGINT), -1),
+            ],
+        };
+
+        assert_eq!(codes.code(ProxyFailure::AppTerminated), -2);
+        assert_eq!(codes.code(ProxyFailure::Signal(SIGINT)), -1);
+        assert_eq!(codes.code(ProxyFailure::CommsError), 214);
+    }
+
+    #[test]
+    fn parse_override_checks_name_and_code() {
+        assert_eq!(
+            parse_override("connect-timeout=-5").unwrap(),
+            (ProxyFailure::ConnectTimeout, -5)
+        );
+        assert!(parse_override("connect-timeout").is_err());
+        assert!(parse_override("unknown=1").is_err());
+        assert!(parse_override("sigterm=abc").is_err());
+    }
+
+    #[test]
+    fn classify_through_context() {
+        let timeout: Result<(), CommsError> = Err(CommsError::WaitOnConnectionTimeOut(
+            std::time::Duration::from_secs(1),
+        ));
+        let report = timeout.wrap_err("No connection").unwrap_err();
+        assert_eq!(
+            ProxyFailure::classify(&report),
+            ProxyFailure::ConnectTimeout
+        );
+
+        let install: Result<(), LabVIEWError> = Err(LabVIEWError::InstallNotFound);
+        let report = install.wrap_err("Failed to launch").unwrap_err();
+        assert_eq!(
+            ProxyFailure::classify(&report),
+            ProxyFailure::InstallNotFound
+        );
+
+        assert_eq!(
+            ProxyFailure::classify(&eyre::eyre!("Logger failed")),
+            ProxyFailure::Internal
+        );
+    }
+
+    #[test]
+    fn map_replaces_codes() {
+        let map = ExitCodeMap {
+            codes: vec![(-1073807346, 3), (5, 6), (5, 7)],
+            overflow: OverflowPolicy::Truncate,
+        };
+
+        assert_eq!(map.translate_with_limit(-1073807346, true), 3);
+        assert_eq!(map.translate_with_limit(5, true), 7);
+        assert_eq!(map.translate_with_limit(4, true), 4);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let policy = |overflow| ExitCodeMap {
+            codes: Vec::new(),
+            overflow,
+        };
+
+        assert_eq!(
+            policy(OverflowPolicy::Truncate).translate_with_limit(-10000, true),
+            -10000
+        );
+        assert_eq!(
+            policy(OverflowPolicy::Clamp).translate_with_limit(-10000, true),
+            1
+        );
+        assert_eq!(
+            policy(OverflowPolicy::Clamp).translate_with_limit(256, true),
+            255
+        );
+        assert_eq!(
+            policy(OverflowPolicy::NonZero).translate_with_limit(256, true),
+            1
+        );
+        assert_eq!(
+            policy(OverflowPolicy::NonZero).translate_with_limit(255, true),
+            255
+        );
+        assert_eq!(
+            policy(OverflowPolicy::NonZero).translate_with_limit(-10000, false),
+            -10000
+        );
+    }
+
+    #[test]
+    fn parse_mapping_needs_two_codes() {
+        assert_eq!(parse_mapping("-1073807346=3").unwrap(), (-1073807346, 3));
+        assert!(parse_mapping("5").is_err());
+        assert!(parse_mapping("5=x").is_err());
+    }
+}
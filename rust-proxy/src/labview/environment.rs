@@ -0,0 +1,197 @@
+//! Controls the environment variables the application is launched with.
+//!
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use super::error::LabVIEWError;
+
+/// Changes to make to the inherited environment when launching the process.
+///
+/// These are applied in order: clear, then unset and then set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnvironmentOptions {
+    /// Start from an empty environment instead of inheriting ours.
+    pub clear: bool,
+    /// Variables to remove from the inherited environment.
+    pub unset: Vec<OsString>,
+    /// Variables to set, later entries override earlier ones.
+    pub set: Vec<(OsString, OsString)>,
+}
+
+impl EnvironmentOptions {
+    /// True if the process should simply inherit our environment.
+    pub fn is_inherited(&self) -> bool {
+        !self.clear && self.unset.is_empty() && self.set.is_empty()
+    }
+
+    /// Adds the variables defined in an env file to the variables to set.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), LabVIEWError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| LabVIEWError::EnvFileFailed(e, path.to_owned()))?;
+        let variables = parse_env_file(&contents)
+            .map_err(|line| LabVIEWError::InvalidEnvFileLine(path.to_owned(), line))?;
+        self.set.extend(variables);
+        Ok(())
+    }
+
+    /// Applies the changes to the parent environment to produce the full child environment.
+    pub fn resolve(
+        &self,
+        parent: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Vec<(OsString, OsString)> {
+        let mut environment: Vec<(OsString, OsString)> = if self.clear {
+            Vec::new()
+        } else {
+            parent
+                .into_iter()
+                .filter(|(key, _)| !self.unset.iter().any(|unset| keys_match(key, unset)))
+                .collect()
+        };
+
+        for (key, value) in &self.set {
+            environment.retain(|(existing, _)| !keys_match(existing, key));
+            environment.push((key.clone(), value.clone()));
+        }
+        environment
+    }
+}
+
+/// Windows treats environment variable names as case insensitive.
+fn keys_match(a: &OsStr, b: &OsStr) -> bool {
+    if cfg!(target_os = "windows") {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// Parses a `KEY=VALUE` argument.
+pub fn parse_variable(variable: &str) -> Result<(OsString, OsString), String> {
+    match variable.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(format!(
+            "\"{variable}\" is not a valid environment variable. Expected KEY=VALUE"
+        )),
+    }
+}
+
+/// Parses the contents of an env file.
+///
+/// Each line is a `KEY=VALUE` pair taken literally. Blank lines and lines starting with `#` are ignored.
+/// Returns the line number of the first invalid line on failure.
+fn parse_env_file(contents: &str) -> Result<Vec<(OsString, OsString)>, usize> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| parse_variable(line).map_err(|_| number))
+        .collect()
+}
+
+/// Builds an environment block for CreateProcessW.
+///
+/// This is a sequence of null terminated `KEY=VALUE` strings sorted by name and ending in an extra null.
+#[cfg(target_os = "windows")]
+pub fn environment_block(environment: &[(OsString, OsString)]) -> Result<Vec<u16>, LabVIEWError> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut sorted: Vec<&(OsString, OsString)> = environment.iter().collect();
+    sorted.sort_by_key(|(key, _)| key.to_ascii_uppercase());
+
+    let mut block = Vec::new();
+    for (key, value) in sorted {
+        let start = block.len();
+        block.extend(key.encode_wide());
+        block.push('=' as u16);
+        block.extend(value.encode_wide());
+        if block[start..].contains(&0) {
+            return Err(LabVIEWError::NullCharInArgument);
+        }
+        block.push(0);
+    }
+    // An empty block still needs two nulls.
+    if block.is_empty() {
+        block.push(0);
+    }
+    block.push(0);
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (OsString::from(key), OsString::from(value)))
+            .collect()
+    }
+
+    #[test]
+    fn parse_variable_splits_on_first_equals() {
+        assert_eq!(
+            parse_variable("OPTS=a=b").unwrap(),
+            (OsString::from("OPTS"), OsString::from("a=b"))
+        );
+        assert_eq!(
+            parse_variable("EMPTY=").unwrap(),
+            (OsString::from("EMPTY"), OsString::new())
+        );
+    }
+
+    #[test]
+    fn parse_variable_rejects_missing_key() {
+        assert!(parse_variable("NOVALUE").is_err());
+        assert!(parse_variable("=value").is_err());
+    }
+
+    #[test]
+    fn parse_env_file_skips_comments_and_blanks() {
+        let contents = "# licence\nLM_LICENSE_FILE=27000@server\n\n  PATH_EXTRA=/opt\n";
+        assert_eq!(
+            parse_env_file(contents).unwrap(),
+            vars(&[("LM_LICENSE_FILE", "27000@server"), ("PATH_EXTRA", "/opt")])
+        );
+    }
+
+    #[test]
+    fn parse_env_file_reports_bad_line() {
+        assert_eq!(parse_env_file("A=1\n# ok\nbroken"), Err(3));
+    }
+
+    #[test]
+    fn resolve_inherits_unsets_and_overrides() {
+        let options = EnvironmentOptions {
+            clear: false,
+            unset: vec![OsString::from("REMOVE")],
+            set: vars(&[("KEEP", "new"), ("ADDED", "1")]),
+        };
+        let parent = vars(&[("KEEP", "old"), ("REMOVE", "x"), ("OTHER", "y")]);
+
+        assert_eq!(
+            options.resolve(parent),
+            vars(&[("OTHER", "y"), ("KEEP", "new"), ("ADDED", "1")])
+        );
+    }
+
+    #[test]
+    fn resolve_clear_drops_parent() {
+        let options = EnvironmentOptions {
+            clear: true,
+            unset: Vec::new(),
+            set: vars(&[("ONLY", "1")]),
+        };
+
+        assert_eq!(
+            options.resolve(vars(&[("HOME", "/root")])),
+            vars(&[("ONLY", "1")])
+        );
+    }
+
+    #[test]
+    fn default_is_inherited() {
+        assert!(EnvironmentOptions::default().is_inherited());
+    }
+}
@@ -9,8 +9,51 @@ pub enum LabVIEWError {
     ServiceLocatorResponseError(u16),
     #[error("Process launch failed")]
     ProcessLaunchFailed(#[source] std::io::Error),
+    #[error("No LabVIEW install found.")]
+    InstallNotFound,
     #[error("VI to launch does not exist: \"{0}\"")]
     ViDoesNotExist(PathBuf),
     #[error("Nul characters in argument isn't allowed")]
     NullCharInArgument,
+    #[error("Failed to create the application output log: \"{1}\"")]
+    OutputLogFailed(#[source] std::io::Error, PathBuf),
+    #[error("Working directory for the application does not exist: \"{0}\"")]
+    WorkingDirectoryDoesNotExist(PathBuf),
+    #[error("Failed to generate the LabVIEW preference file from: \"{1}\"")]
+    PreferenceFileFailed(#[source] std::io::Error, PathBuf),
+    #[error("Failed to create the isolated run directory: \"{1}\"")]
+    IsolationFailed(#[source] std::io::Error, PathBuf),
+    #[error("The argument \"{0}\" is managed by G-CLI so can't be passed as an app argument")]
+    ManagedArgument(String),
+    #[error("No X display is available as DISPLAY is not set and Xvfb could not be found. Install Xvfb (e.g. the xvfb package), set DISPLAY to an existing display or use --no-virtual-display")]
+    XvfbNotFound,
+    #[error("Failed to start a virtual display with Xvfb: {0}")]
+    VirtualDisplayFailed(String),
+    #[error("Failed to read the environment file: \"{1}\"")]
+    EnvFileFailed(#[source] std::io::Error, PathBuf),
+    #[error("Invalid line in the environment file \"{0}\" at line {1}. Expected KEY=VALUE")]
+    InvalidEnvFileLine(PathBuf, usize),
+}
+
+impl LabVIEWError {
+    /// The name of the variant, e.g. for scripts to match on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ServiceLocatorCommsError(..) => "ServiceLocatorCommsError",
+            Self::ServiceLocatorResponseError(..) => "ServiceLocatorResponseError",
+            Self::ProcessLaunchFailed(..) => "ProcessLaunchFailed",
+            Self::InstallNotFound => "InstallNotFound",
+            Self::ViDoesNotExist(..) => "ViDoesNotExist",
+            Self::NullCharInArgument => "NullCharInArgument",
+            Self::OutputLogFailed(..) => "OutputLogFailed",
+            Self::WorkingDirectoryDoesNotExist(..) => "WorkingDirectoryDoesNotExist",
+            Self::PreferenceFileFailed(..) => "PreferenceFileFailed",
+            Self::IsolationFailed(..) => "IsolationFailed",
+            Self::ManagedArgument(..) => "ManagedArgument",
+            Self::XvfbNotFound => "XvfbNotFound",
+            Self::VirtualDisplayFailed(..) => "VirtualDisplayFailed",
+            Self::EnvFileFailed(..) => "EnvFileFailed",
+            Self::InvalidEnvFileLine(..) => "InvalidEnvFileLine",
+        }
+    }
 }
@@ -14,6 +14,17 @@ pub enum LabviewInstallError {
     DirectoryError(#[source] std::io::Error, String),
 }
 
+impl LabviewInstallError {
+    /// The name of the variant, e.g. for scripts to match on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            #[cfg(target_os = "windows")]
+            Self::RegKeyError(..) => "RegKeyError",
+            Self::DirectoryError(..) => "DirectoryError",
+        }
+    }
+}
+
 /// Defines if LabVIEW is 64 bit or 32 bit.
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub enum Bitness {
@@ -69,6 +80,16 @@ impl LabviewInstall {
     pub fn application_path(&self) -> PathBuf {
         self.path.join(LABVIEW_EXE)
     }
+
+    /// Get the default preference file LabVIEW loads, if we know where it is.
+    pub fn preference_path(&self) -> Option<PathBuf> {
+        if cfg!(target_os = "windows") {
+            Some(self.path.join("LabVIEW.ini"))
+        } else {
+            // Linux keeps these in the home directory of the user.
+            None
+        }
+    }
 }
 
 /// Stores the full system installation details for LabVIEW.
@@ -119,6 +140,11 @@ impl SystemLabviewInstalls {
         output
     }
 
+    /// All of the detected installs.
+    pub fn installs(&self) -> impl Iterator<Item = &LabviewInstall> {
+        self.versions.values()
+    }
+
     /// Get a default version which is just the latest.
     pub fn get_default(&self) -> Option<&LabviewInstall> {
         self.versions.values().last()
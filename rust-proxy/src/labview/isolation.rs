@@ -0,0 +1,145 @@
+//! Private directories for a single run so parallel runs of LabVIEW don't share
+//! temp files, caches or preferences.
+//!
+use log::{debug, info, warn};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use super::error::LabVIEWError;
+
+/// The directories for an isolated run. These are deleted when dropped.
+#[derive(Debug)]
+pub struct IsolatedRun {
+    root: PathBuf,
+}
+
+impl IsolatedRun {
+    /// Creates the directories for this run in the system temp directory.
+    pub fn create(port: u16) -> Result<Self, LabVIEWError> {
+        let root =
+            std::env::temp_dir().join(format!("g-cli-isolated-{}-{}", std::process::id(), port));
+        let run = Self { root };
+
+        for dir in [run.temp_dir(), run.data_dir(), run.preference_dir()] {
+            std::fs::create_dir_all(&dir).map_err(|e| LabVIEWError::IsolationFailed(e, dir))?;
+        }
+        debug!("Created isolated run directories in {}", run.root.display());
+
+        Ok(run)
+    }
+
+    pub fn temp_dir(&self) -> PathBuf {
+        self.root.join("temp")
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.root.join("data")
+    }
+
+    pub fn preference_dir(&self) -> PathBuf {
+        self.root.join("preferences")
+    }
+
+    /// Leaves the directories in place for an application which outlives us.
+    pub fn keep(self) {
+        info!(
+            "Leaving the isolated run directories in {}",
+            self.root.display()
+        );
+        std::mem::forget(self);
+    }
+
+    /// The preferences which point LabVIEW at the private directories.
+    ///
+    /// The LabVIEW Data folder, which holds VIObjCache and LVAutoSave, is under Documents on Windows
+    /// and no environment variable moves it. LabVIEW's temp directory follows `TEMP`.
+    pub fn preferences(&self) -> Vec<(String, String)> {
+        vec![(
+            String::from("DefaultDataFileLocation"),
+            self.data_dir().to_string_lossy().into_owned(),
+        )]
+    }
+
+    /// The environment variables which point LabVIEW at the private directories.
+    pub fn environment(&self) -> Vec<(OsString, OsString)> {
+        let temp = self.temp_dir().into_os_string();
+        let data = self.data_dir().into_os_string();
+
+        let mut environment = vec![
+            (OsString::from("TEMP"), temp.clone()),
+            (OsString::from("TMP"), temp.clone()),
+            (OsString::from("TMPDIR"), temp),
+        ];
+
+        if cfg!(target_os = "windows") {
+            // Caches such as VIObjCache live in the app data folders.
+            environment.push((OsString::from("APPDATA"), data.clone()));
+            environment.push((OsString::from("LOCALAPPDATA"), data));
+        } else {
+            // X clients look for their credentials in HOME so keep them pointing at the real one.
+            if let (None, Some(home)) = (std::env::var_os("XAUTHORITY"), std::env::var_os("HOME")) {
+                let xauthority = PathBuf::from(home).join(".Xauthority");
+                environment.push((OsString::from("XAUTHORITY"), xauthority.into_os_string()));
+            }
+            // LabVIEW keeps its data and caches in ~/natinst.
+            environment.push((OsString::from("HOME"), data));
+        }
+
+        environment
+    }
+}
+
+impl Drop for IsolatedRun {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.root) {
+            warn!(
+                "Failed to remove the isolated run directory {}: {}",
+                self.root.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_removed_on_drop() {
+        let run = IsolatedRun::create(1).unwrap();
+        let root = run.root.clone();
+
+        assert!(run.temp_dir().is_dir());
+        assert!(run.data_dir().is_dir());
+        assert!(run.preference_dir().is_dir());
+
+        std::fs::write(run.temp_dir().join("cache.tmp"), "data").unwrap();
+        drop(run);
+
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn environment_points_to_temp_dir() {
+        let run = IsolatedRun::create(2).unwrap();
+        let environment = run.environment();
+
+        assert!(environment
+            .iter()
+            .any(|(key, value)| key == "TMP" && *value == run.temp_dir().into_os_string()));
+    }
+
+    #[test]
+    fn preferences_point_to_data_dir() {
+        let run = IsolatedRun::create(3).unwrap();
+
+        assert_eq!(
+            run.preferences(),
+            vec![(
+                String::from("DefaultDataFileLocation"),
+                run.data_dir().to_string_lossy().into_owned()
+            )]
+        );
+    }
+}
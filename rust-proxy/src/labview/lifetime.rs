@@ -0,0 +1,318 @@
+//! Ties the lifetime of the launched process group to G-CLI on Linux.
+//!
+//! The launched process is killed by the kernel if G-CLI dies, and each run is
+//! recorded on disk so anything left behind by a run that was killed can be
+//! cleaned up by the next.
+use log::{debug, info, warn};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// A record of this run on disk. When dropped the process group is killed and the record removed.
+#[derive(Debug)]
+pub struct RunRecord {
+    path: PathBuf,
+    group: u32,
+}
+
+/// The identity of a process which survives PID reuse.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ProcessIdentity {
+    pid: u32,
+    /// Start time in clock ticks since boot.
+    start_time: u64,
+}
+
+impl ProcessIdentity {
+    fn of(pid: u32) -> Option<Self> {
+        let stat = read_stat(pid)?;
+        Some(Self {
+            pid,
+            start_time: stat.start_time,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        Self::of(self.pid).as_ref() == Some(self)
+    }
+}
+
+/// The directory runs are recorded in.
+///
+/// This is per user so other users can't plant records for us to act on.
+pub fn runs_directory() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(runtime) if runtime.is_absolute() => runtime.join("g-cli-runs"),
+        _ => std::env::temp_dir().join(format!("g-cli-runs-{}", current_uid())),
+    }
+}
+
+fn current_uid() -> u32 {
+    // Safety: getuid has no memory safety requirements and can't fail.
+    unsafe { libc::getuid() }
+}
+
+/// Creates the runs directory so only we can use it, or checks an existing one is ours and private.
+fn create_private_directory(directory: &Path) -> std::io::Result<()> {
+    if let Err(e) = std::fs::DirBuilder::new().mode(0o700).create(directory) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e);
+        }
+    }
+
+    let metadata = std::fs::symlink_metadata(directory)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "{} must be a directory owned by this user and not accessible by others",
+                directory.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+impl RunRecord {
+    /// Records that this G-CLI owns the process group led by `group`.
+    pub fn create(directory: &Path, group: u32) -> std::io::Result<Self> {
+        let owner = ProcessIdentity::of(std::process::id()).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "Can't read our process")
+        })?;
+        let leader = ProcessIdentity::of(group).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "Launched process has gone")
+        })?;
+
+        create_private_directory(directory)?;
+        let path = directory.join(format!("{}.run", owner.pid));
+        std::fs::write(
+            &path,
+            format!(
+                "{} {} {} {}\n",
+                owner.pid, owner.start_time, leader.pid, leader.start_time
+            ),
+        )?;
+        debug!("Recorded run in {}", path.display());
+
+        Ok(Self { path, group })
+    }
+}
+
+impl Drop for RunRecord {
+    fn drop(&mut self) {
+        kill_group(self.group);
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove run record {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Kills the process groups left behind by runs whose G-CLI is no longer alive.
+///
+/// Only records written by the current user are acted on.
+pub fn clean_stale_runs(directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+
+    let uid = current_uid();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension() != Some(std::ffi::OsStr::new("run")) {
+            continue;
+        }
+
+        let owned = std::fs::symlink_metadata(&path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.uid() == uid);
+        if !owned {
+            warn!(
+                "Ignoring run record {} as it isn't owned by this user",
+                path.display()
+            );
+            continue;
+        }
+
+        let record = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| parse_record(&contents));
+        let Some((owner, leader)) = record else {
+            warn!("Removing unreadable run record {}", path.display());
+            let _ = std::fs::remove_file(&path);
+            continue;
+        };
+
+        if owner.is_alive() {
+            continue;
+        }
+
+        // The group ID can't be reused while the group has members, so only the leader needs checking.
+        let leader_reused =
+            ProcessIdentity::of(leader.pid).is_some_and(|current| current != leader);
+        if !leader_reused && !group_members(leader.pid).is_empty() {
+            info!(
+                "Killing process group {} left behind by G-CLI process {} which is no longer running",
+                leader.pid, owner.pid
+            );
+            kill_group(leader.pid);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
+
+/// Kills every process in the group if it still exists.
+fn kill_group(group: u32) {
+    if group_members(group).is_empty() {
+        return;
+    }
+    // Safety: killpg has no memory safety requirements.
+    unsafe {
+        libc::killpg(group as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+/// Finds the processes in the process group.
+fn group_members(group: u32) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| read_stat(*pid).is_some_and(|stat| stat.group == group))
+        .collect()
+}
+
+/// Parses a run record into the G-CLI process and the leader of the group it launched.
+fn parse_record(contents: &str) -> Option<(ProcessIdentity, ProcessIdentity)> {
+    let values: Vec<u64> = contents
+        .split_whitespace()
+        .map(|value| value.parse().ok())
+        .collect::<Option<_>>()?;
+    match values[..] {
+        [owner, owner_start, leader, leader_start] => Some((
+            ProcessIdentity {
+                pid: owner as u32,
+                start_time: owner_start,
+            },
+            ProcessIdentity {
+                pid: leader as u32,
+                start_time: leader_start,
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// The fields we need from `/proc/<pid>/stat`.
+#[derive(Debug, PartialEq)]
+struct ProcStat {
+    group: u32,
+    start_time: u64,
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat(&stat)
+}
+
+/// Parses `/proc/<pid>/stat`.
+///
+/// The name is in brackets and can contain spaces, so the fields are counted after the last bracket.
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    // Fields start at 3 (state) so the process group (5) is index 2 and start time (22) is index 19.
+    Some(ProcStat {
+        group: fields.get(2)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    #[test]
+    fn parse_stat_with_spaces_in_name() {
+        let stat = "1234 (Lab VIEW) S 1 1234 1234 0 -1 4194560 100 0 0 0 5 3 0 0 20 0 4 0 98765 1000 200 18446744073709551615";
+
+        assert_eq!(
+            parse_stat(stat),
+            Some(ProcStat {
+                group: 1234,
+                start_time: 98765
+            })
+        );
+    }
+
+    #[test]
+    fn parse_record_round_trip() {
+        let (owner, leader) = parse_record("10 100 20 200\n").unwrap();
+
+        assert_eq!(
+            owner,
+            ProcessIdentity {
+                pid: 10,
+                start_time: 100
+            }
+        );
+        assert_eq!(
+            leader,
+            ProcessIdentity {
+                pid: 20,
+                start_time: 200
+            }
+        );
+        assert_eq!(parse_record("10 100"), None);
+    }
+
+    #[test]
+    fn private_directory_is_only_accessible_by_us() {
+        let directory =
+            std::env::temp_dir().join(format!("g-cli-runs-private-{}", std::process::id()));
+
+        create_private_directory(&directory).unwrap();
+        let mode = std::fs::metadata(&directory).unwrap().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        // An existing directory is fine as long as it is still private.
+        create_private_directory(&directory).unwrap();
+
+        std::fs::set_permissions(
+            &directory,
+            std::os::unix::fs::PermissionsExt::from_mode(0o777),
+        )
+        .unwrap();
+        assert!(create_private_directory(&directory).is_err());
+        std::fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn stale_run_group_is_killed() {
+        let directory =
+            std::env::temp_dir().join(format!("g-cli-runs-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        // A G-CLI which has exited.
+        let mut owner = std::process::Command::new("true").spawn().unwrap();
+        let owner_pid = owner.id();
+        owner.wait().unwrap();
+
+        let mut orphan = std::process::Command::new("sleep")
+            .arg("1000")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let leader = ProcessIdentity::of(orphan.id()).unwrap();
+        std::fs::write(
+            directory.join(format!("{owner_pid}.run")),
+            format!("{} 1 {} {}\n", owner_pid, leader.pid, leader.start_time),
+        )
+        .unwrap();
+
+        clean_stale_runs(&directory);
+
+        let status = orphan.wait().unwrap();
+        assert!(!status.success());
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+        std::fs::remove_dir(&directory).unwrap();
+    }
+}
@@ -3,11 +3,17 @@
 //! `labview` contains functionality for finding, launching
 //! and monitoring the labview process.
 
+pub mod environment;
 pub mod error;
 pub mod installs;
+pub mod isolation;
+#[cfg(target_os = "linux")]
+mod lifetime;
 mod port_discovery;
+pub mod preferences;
 pub mod process;
 mod vi_location;
+pub mod virtual_display;
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 pub mod install_detection_linux;
@@ -20,27 +26,72 @@ pub use install_detection_linux::*;
 pub use install_detection_win::*;
 
 use log::debug;
+use std::sync::{atomic::AtomicBool, mpsc::Sender, Arc};
 use std::{ffi::OsString, path::PathBuf};
 
+use crate::action_loop::ActionMessage;
 use crate::os_string_support::join_os_string;
 use port_discovery::Registration;
+use preferences::PreferenceFile;
 use vi_location::VILocation;
 
 use self::error::LabVIEWError;
 
-fn create_args(port: u16, allow_dialogs: bool) -> Vec<OsString> {
+fn create_args(port: u16, allow_dialogs: bool, app_args: &[OsString]) -> Vec<OsString> {
     let mut args = vec![];
     if !allow_dialogs {
         args.push(OsString::from("-unattended"));
     }
+    args.extend_from_slice(app_args);
 
     args.push(OsString::from("--"));
     args.push(OsString::from(format!("-p:{}", port)));
     args
 }
 
-pub fn launch_exe(path: PathBuf, port: u16) -> Result<process::MonitoredProcess, LabVIEWError> {
-    process::MonitoredProcess::start(path, &create_args(port, true), None)
+/// Checks the extra app arguments don't clash with the ones we add ourselves.
+fn check_app_args(options: &process::LaunchOptions) -> Result<(), LabVIEWError> {
+    for arg in &options.app_args {
+        let arg_str = arg.to_string_lossy();
+        let managed = arg_str == "--"
+            || arg_str.eq_ignore_ascii_case("-unattended")
+            || arg_str.to_ascii_lowercase().starts_with("-p:")
+            || (options.preferences.is_some() && arg_str.eq_ignore_ascii_case("-pref"));
+
+        if managed {
+            return Err(LabVIEWError::ManagedArgument(arg_str.into_owned()));
+        }
+    }
+    Ok(())
+}
+
+pub fn launch_exe(
+    path: PathBuf,
+    port: u16,
+    options: &process::LaunchOptions,
+    action_channel: Sender<ActionMessage>,
+    app_stop: Arc<AtomicBool>,
+) -> Result<process::MonitoredProcess, LabVIEWError> {
+    check_app_args(options)?;
+
+    // A relative path would be resolved against the new working directory so fix it to ours.
+    let path = if options.working_dir.is_some() && path.is_relative() {
+        std::env::current_dir()
+            .map_err(LabVIEWError::ProcessLaunchFailed)?
+            .join(path)
+    } else {
+        path
+    };
+
+    process::MonitoredProcess::start(
+        path,
+        &create_args(port, true, &options.app_args),
+        options,
+        None,
+        None,
+        action_channel,
+        app_stop,
+    )
 }
 
 pub fn launch_lv(
@@ -48,7 +99,12 @@ pub fn launch_lv(
     launch_vi: PathBuf,
     port: u16,
     allow_dialogs: bool,
+    options: &process::LaunchOptions,
+    action_channel: Sender<ActionMessage>,
+    app_stop: Arc<AtomicBool>,
 ) -> Result<process::MonitoredProcess, LabVIEWError> {
+    check_app_args(options)?;
+
     let mut vi = VILocation::new(&launch_vi);
 
     if !vi.exists() {
@@ -70,8 +126,24 @@ pub fn launch_lv(
     let registration = Registration::register(&vi, install, &port)?;
 
     //todo: unwrap could fail here, can we validate it?
-    let mut lv_args = vec![vi.labview_parameter()];
-    lv_args.append(&mut create_args(port, allow_dialogs));
+    // LabVIEW resolves relative VI paths against its working directory so use the full path if we change it.
+    let vi_parameter = if options.working_dir.is_some() {
+        vi.canonical_vi_path().into_os_string()
+    } else {
+        vi.labview_parameter()
+    };
+    let preferences = match &options.preferences {
+        Some(preferences) => Some(PreferenceFile::create(preferences, install, port)?),
+        None => None,
+    };
+
+    let mut lv_args = vec![];
+    if let Some(preferences) = &preferences {
+        lv_args.push(OsString::from("-pref"));
+        lv_args.push(preferences.path().as_os_str().to_owned());
+    }
+    lv_args.push(vi_parameter);
+    lv_args.append(&mut create_args(port, allow_dialogs, &options.app_args));
 
     let path = install.application_path();
 
@@ -81,7 +153,15 @@ pub fn launch_lv(
         join_os_string(&lv_args, " ")
     );
 
-    process::MonitoredProcess::start(path, &lv_args, Some(registration))
+    process::MonitoredProcess::start(
+        path,
+        &lv_args,
+        options,
+        Some(registration),
+        preferences,
+        action_channel,
+        app_stop,
+    )
 }
 
 #[cfg(test)]
@@ -91,7 +171,7 @@ mod tests {
 
     #[test]
     fn test_args_with_port() {
-        let args = create_args(1234, false);
+        let args = create_args(1234, false, &[]);
 
         let expected = vec![
             OsString::from("-unattended"),
@@ -104,10 +184,63 @@ mod tests {
 
     #[test]
     fn test_args_no_dialog() {
-        let args = create_args(1234, true);
+        let args = create_args(1234, true, &[]);
 
         let expected = vec![OsString::from("--"), OsString::from("-p:1234")];
 
         assert_eq!(args, expected);
     }
+
+    #[test]
+    fn test_args_with_app_args() {
+        let args = create_args(1234, false, &[OsString::from("-nosplash")]);
+
+        let expected = vec![
+            OsString::from("-unattended"),
+            OsString::from("-nosplash"),
+            OsString::from("--"),
+            OsString::from("-p:1234"),
+        ];
+
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn test_app_args_allowed() {
+        let options = process::LaunchOptions {
+            app_args: vec![OsString::from("-nosplash"), OsString::from("-pref")],
+            ..Default::default()
+        };
+
+        assert!(check_app_args(&options).is_ok());
+    }
+
+    #[test]
+    fn test_app_args_managed_by_gcli() {
+        for arg in ["--", "-Unattended", "-p:5000"] {
+            let options = process::LaunchOptions {
+                app_args: vec![OsString::from(arg)],
+                ..Default::default()
+            };
+
+            assert!(matches!(
+                check_app_args(&options),
+                Err(LabVIEWError::ManagedArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_app_args_pref_clash_with_generated_preferences() {
+        let options = process::LaunchOptions {
+            app_args: vec![OsString::from("-pref")],
+            preferences: Some(Default::default()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            check_app_args(&options),
+            Err(LabVIEWError::ManagedArgument(_))
+        ));
+    }
 }
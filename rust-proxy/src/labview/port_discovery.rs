@@ -1,11 +1,16 @@
 use super::error::LabVIEWError;
 use super::installs::LabviewInstall;
 use super::vi_location::VILocation;
+use log::warn;
 use std::path::Path;
 use ureq::get;
 
+/// A port published with the service locator.
+///
+/// If it is dropped without being unregistered, for example because the launch failed, it is removed then.
 pub struct Registration {
     id: String,
+    registered: bool,
 }
 
 impl Registration {
@@ -31,12 +36,20 @@ impl Registration {
         if status_code > 299 {
             Err(LabVIEWError::ServiceLocatorResponseError(status_code))
         } else {
-            Ok(Registration { id })
+            Ok(Registration {
+                id,
+                registered: true,
+            })
         }
     }
 
     /// Unregisters the port with the service locator and consumes the registration object.
-    pub fn unregister(self) -> Result<(), LabVIEWError> {
+    pub fn unregister(mut self) -> Result<(), LabVIEWError> {
+        self.registered = false;
+        self.delete()
+    }
+
+    fn delete(&self) -> Result<(), LabVIEWError> {
         let response = get(&format!("http://localhost:3580/delete?{}", self.id))
             .call()
             .map_err(|e| LabVIEWError::ServiceLocatorCommsError(Box::new(e)))?;
@@ -51,6 +64,16 @@ impl Registration {
     }
 }
 
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if self.registered {
+            if let Err(e) = self.delete() {
+                warn!("Failed to remove the service locator registration: {}", e);
+            }
+        }
+    }
+}
+
 /// Generates an ID unique to the install and VI path.
 /// Path should be the full path to the VI.
 fn generate_registration_id(vi_path: &Path, install: &LabviewInstall) -> String {
@@ -0,0 +1,215 @@
+//! Generates a LabVIEW preference (INI) file for a single run.
+//!
+//! This lets us change LabVIEW settings without touching the machine-wide `LabVIEW.ini`.
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+
+use super::error::LabVIEWError;
+use super::installs::LabviewInstall;
+
+/// The section LabVIEW reads its own settings from.
+const LABVIEW_SECTION: &str = "LabVIEW";
+
+/// Settings for generating the preference file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PreferenceOptions {
+    /// File to copy the settings from. Defaults to the preference file of the install.
+    pub base: Option<PathBuf>,
+    /// Keys to set in the LabVIEW section, later entries override earlier ones.
+    pub overrides: Vec<(String, String)>,
+    /// Directory to write the file to. Defaults to the temp directory.
+    pub directory: Option<PathBuf>,
+}
+
+/// A generated preference file which is deleted when dropped.
+#[derive(Debug)]
+pub struct PreferenceFile {
+    path: PathBuf,
+}
+
+impl PreferenceFile {
+    /// Writes the preference file for this install to the temp directory.
+    pub fn create(
+        options: &PreferenceOptions,
+        install: &LabviewInstall,
+        port: u16,
+    ) -> Result<Self, LabVIEWError> {
+        let base_path = options
+            .base
+            .clone()
+            .or_else(|| install.preference_path().filter(|path| path.exists()));
+
+        let base = match &base_path {
+            Some(path) => {
+                debug!("Using {} as the base preference file", path.display());
+                std::fs::read_to_string(path)
+                    .map_err(|e| LabVIEWError::PreferenceFileFailed(e, path.clone()))?
+            }
+            None => String::new(),
+        };
+
+        let path = match &options.directory {
+            Some(directory) => directory.join("LabVIEW.ini"),
+            None => std::env::temp_dir().join(format!("g-cli-{}-{}.ini", std::process::id(), port)),
+        };
+        std::fs::write(&path, apply_overrides(&base, &options.overrides))
+            .map_err(|e| LabVIEWError::PreferenceFileFailed(e, path.clone()))?;
+        debug!("Generated preference file {}", path.display());
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the file in place for an application which outlives us.
+    pub fn keep(self) {
+        info!(
+            "Leaving the preference file {} in place",
+            self.path.display()
+        );
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for PreferenceFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove the preference file {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Parses a `key=value` preference argument.
+pub fn parse_preference(preference: &str) -> Result<(String, String), String> {
+    match preference.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_owned(), value.to_owned()))
+        }
+        _ => Err(format!(
+            "\"{preference}\" is not a valid preference. Expected key=value"
+        )),
+    }
+}
+
+/// Sets the keys in the LabVIEW section of the INI contents.
+///
+/// Existing keys are replaced in place and new keys are added to the end of the section.
+/// The section is added if it doesn't exist.
+fn apply_overrides(base: &str, overrides: &[(String, String)]) -> String {
+    let newline = if base.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<String> = base.lines().map(str::to_owned).collect();
+
+    let section_start = lines
+        .iter()
+        .position(|line| is_section(line, LABVIEW_SECTION));
+    let section_start = match section_start {
+        Some(index) => index,
+        None => {
+            lines.push(format!("[{LABVIEW_SECTION}]"));
+            lines.len() - 1
+        }
+    };
+
+    for (key, value) in overrides {
+        let entry = format!("{key}={value}");
+        let section_end = lines[section_start + 1..]
+            .iter()
+            .position(|line| line.trim_start().starts_with('['))
+            .map_or(lines.len(), |offset| section_start + 1 + offset);
+
+        let existing = lines[section_start + 1..section_end]
+            .iter()
+            .position(|line| line_key(line).is_some_and(|k| k.eq_ignore_ascii_case(key)));
+
+        match existing {
+            Some(offset) => lines[section_start + 1 + offset] = entry,
+            None => {
+                // Keep the new keys with the others rather than after any trailing blank lines.
+                let mut insert_at = section_end;
+                while insert_at > section_start + 1 && lines[insert_at - 1].trim().is_empty() {
+                    insert_at -= 1;
+                }
+                lines.insert(insert_at, entry);
+            }
+        }
+    }
+
+    let mut contents = lines.join(newline);
+    contents.push_str(newline);
+    contents
+}
+
+fn is_section(line: &str, name: &str) -> bool {
+    line.trim()
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .is_some_and(|section| section.trim().eq_ignore_ascii_case(name))
+}
+
+fn line_key(line: &str) -> Option<&str> {
+    line.split_once('=').map(|(key, _)| key.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_preference_trims_key() {
+        assert_eq!(
+            parse_preference(" server.tcp.enabled =False").unwrap(),
+            (String::from("server.tcp.enabled"), String::from("False"))
+        );
+        assert!(parse_preference("noequals").is_err());
+        assert!(parse_preference("=value").is_err());
+    }
+
+    #[test]
+    fn overrides_replace_existing_keys() {
+        let base = "[LabVIEW]\nserver.tcp.enabled=True\nAutoErrorHandling=True\n";
+
+        assert_eq!(
+            apply_overrides(base, &overrides(&[("SERVER.TCP.ENABLED", "False")])),
+            "[LabVIEW]\nSERVER.TCP.ENABLED=False\nAutoErrorHandling=True\n"
+        );
+    }
+
+    #[test]
+    fn overrides_add_to_end_of_section() {
+        let base = "[LabVIEW]\nkey=1\n\n[Other]\nTempDir=keep\n";
+
+        assert_eq!(
+            apply_overrides(base, &overrides(&[("TempDir", "/tmp/lv")])),
+            "[LabVIEW]\nkey=1\nTempDir=/tmp/lv\n\n[Other]\nTempDir=keep\n"
+        );
+    }
+
+    #[test]
+    fn overrides_create_missing_section() {
+        assert_eq!(
+            apply_overrides("", &overrides(&[("AutoErrorHandling", "False")])),
+            "[LabVIEW]\nAutoErrorHandling=False\n"
+        );
+    }
+
+    #[test]
+    fn overrides_keep_windows_line_endings() {
+        assert_eq!(
+            apply_overrides("[LabVIEW]\r\na=1\r\n", &overrides(&[("b", "2")])),
+            "[LabVIEW]\r\na=1\r\nb=2\r\n"
+        );
+    }
+}
@@ -1,94 +1,378 @@
-use super::{error::LabVIEWError, Registration};
-use log::{debug, info};
-use std::collections::HashMap;
+use super::preferences::{PreferenceFile, PreferenceOptions};
+use super::{environment::EnvironmentOptions, error::LabVIEWError, Registration};
+use crate::action_loop::ActionMessage;
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
-use std::thread::{sleep, JoinHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+use sysinfo::{Pid, PidExt, ProcessExt, ProcessRefreshKind, Signal, System, SystemExt};
 
-const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+/// How often we check on a process we can't get exit notifications for.
+/// This also limits how long the action loop can be held open after it stops.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often we scan every process for LabVIEW while the launcher is still running.
+const LAUNCHER_SCAN_INTERVAL: Duration = Duration::from_secs(1);
 
-// TODO: There are definately improvements to the process monitoring. For example reusing the system item.
+/// Options controlling how the process is launched.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptions {
+    /// Capture the standard output and error of the process into this file.
+    /// Only supported on Linux.
+    pub output_log: Option<PathBuf>,
+    /// How the process is terminated if we have to kill it.
+    pub termination: TerminationPolicy,
+    /// Allow killing an instance that was already running before we launched.
+    pub kill_existing: bool,
+    /// Changes to the environment the process inherits from us.
+    pub environment: EnvironmentOptions,
+    /// Working directory for the process. It inherits ours if this is None.
+    pub working_dir: Option<PathBuf>,
+    /// Generate a preference file for this run. Only used when launching LabVIEW.
+    pub preferences: Option<PreferenceOptions>,
+    /// Extra arguments for LabVIEW or the executable, placed before the `--` separator.
+    pub app_args: Vec<OsString>,
+    /// Command and arguments to run the application through, such as `xvfb-run` or `nice`.
+    pub launcher: Option<Vec<OsString>>,
+    /// Kill the process if G-CLI dies and clean up after previous runs that did. Only supported on Linux.
+    pub die_with_parent: bool,
+}
+
+/// How the process is terminated when we kill it.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum TerminationPolicy {
+    /// Kill the process straight away.
+    #[default]
+    Immediate,
+    /// Ask the process to terminate (SIGTERM) and only kill it if it is still running after the grace period.
+    /// Windows has no equivalent so this will kill immediately.
+    Graceful(Duration),
+}
+
+/// Events processed by the monitoring thread.
+enum MonitorEvent {
+    /// Stop monitoring. If a timeout is provided the process is killed if it is still active after it.
+    /// The termination policy overrides the one the process was launched with.
+    Stop(Option<Duration>, Option<TerminationPolicy>),
+    /// Stop monitoring, asking the process to terminate and applying the termination policy if it is still active after the grace period.
+    Cancel(Duration),
+    /// The process we launched directly has exited, with its resource usage if available.
+    ChildExited(Option<ResourceUsage>),
+    /// The application has connected to us.
+    Connected,
+}
+
+/// Resource usage reported by the OS for a process.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ResourceUsage {
+    /// Peak resident memory in bytes.
+    peak_memory: u64,
+    /// Total user and system CPU time.
+    cpu_time: Duration,
+}
+
+/// Resource usage statistics for the monitored process over the run.
+#[derive(Clone, Debug)]
+pub struct ProcessStats {
+    /// Peak resident memory in bytes, if it could be measured.
+    pub peak_memory: Option<u64>,
+    /// Total CPU time, if it could be measured.
+    pub cpu_time: Option<Duration>,
+    /// Time from launch until the process exited or we stopped monitoring it.
+    pub wall_time: Duration,
+}
+
+impl fmt::Display for ProcessStats {
+    /// Format the stats as a human readable summary.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Application Statistics:")?;
+        writeln!(f, "  Wall-clock time: {:.3}s", self.wall_time.as_secs_f64())?;
+        match self.cpu_time {
+            Some(cpu_time) => writeln!(f, "  CPU time: {:.3}s", cpu_time.as_secs_f64())?,
+            None => writeln!(f, "  CPU time: Not available")?,
+        }
+        match self.peak_memory {
+            Some(bytes) => writeln!(
+                f,
+                "  Peak memory: {:.1} MiB",
+                bytes as f64 / (1024.0 * 1024.0)
+            ),
+            None => writeln!(f, "  Peak memory: Not available"),
+        }
+    }
+}
+
+/// Collects the resource usage while we monitor the process.
+struct StatsCollector {
+    started: Instant,
+    ended: Option<Instant>,
+    peak_memory: Option<u64>,
+    cpu_time: Option<Duration>,
+}
+
+impl StatsCollector {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            ended: None,
+            peak_memory: None,
+            cpu_time: None,
+        }
+    }
+
+    /// Record a memory sample, keeping the peak.
+    fn sample_memory(&mut self, bytes: u64) {
+        self.peak_memory = Some(self.peak_memory.map_or(bytes, |peak| peak.max(bytes)));
+    }
+
+    /// Record usage reported by the OS. This is cumulative so replaces the CPU time.
+    fn record_usage(&mut self, usage: ResourceUsage) {
+        self.sample_memory(usage.peak_memory);
+        self.cpu_time = Some(usage.cpu_time);
+    }
+
+    /// Mark the process as finished for the wall-clock time.
+    fn finish(&mut self) {
+        self.ended.get_or_insert_with(Instant::now);
+    }
+
+    fn stats(&self) -> ProcessStats {
+        let ended = self.ended.unwrap_or_else(Instant::now);
+        ProcessStats {
+            peak_memory: self.peak_memory,
+            cpu_time: self.cpu_time,
+            wall_time: ended - self.started,
+        }
+    }
+}
 
 pub struct MonitoredProcess {
-    stop_channel: mpsc::Sender<Option<Duration>>,
+    stop_channel: mpsc::Sender<MonitorEvent>,
     /// Port registration for management
     /// Im not totally convinced this is the right place for it.
     port_registration: Option<Registration>,
-    monitor_thread: JoinHandle<()>,
+    /// Kept until we stop monitoring so the file is removed afterwards.
+    preferences: Option<PreferenceFile>,
+    /// Kept until we stop monitoring so the process group is cleaned up afterwards.
+    #[cfg(target_os = "linux")]
+    _run_record: Option<super::lifetime::RunRecord>,
+    monitor_thread: JoinHandle<ProcessStats>,
+    /// Set once the process is lost so we can abort waiting on it.
+    exited: Arc<AtomicBool>,
 }
 
 impl MonitoredProcess {
+    /// Launch the process and start monitoring it.
+    ///
+    /// If the process is lost a [`ActionMessage::ProcessExited`] is sent to the action loop.
+    /// The channel is released once `app_stop` is set so it doesn't hold the action loop open.
     pub fn start(
         path: PathBuf,
         args: &[OsString],
+        options: &LaunchOptions,
         port_registration: Option<Registration>,
+        preferences: Option<PreferenceFile>,
+        action_channel: Sender<ActionMessage>,
+        app_stop: Arc<AtomicBool>,
     ) -> Result<Self, LabVIEWError> {
-        let original_pid = launch(&path, args)?;
+        if let Some(dir) = &options.working_dir {
+            if !dir.is_dir() {
+                return Err(LabVIEWError::WorkingDirectoryDoesNotExist(dir.clone()));
+            }
+        }
 
-        //setup a channel for passing stop messages//
-        let (stop_tx, stop_rx) = mpsc::channel::<Option<Duration>>();
+        //setup a channel for passing stop and exit messages//
+        let (event_tx, event_rx) = mpsc::channel::<MonitorEvent>();
 
-        let thread_path = path;
+        // Take the snapshot of existing instances before we launch ours.
+        let mut tracker = ProcessTracker::new(path.clone(), options.kill_existing);
+
+        // On Linux we own the child so can wait on it directly rather than polling the system.
+        #[cfg(target_os = "linux")]
+        if options.die_with_parent {
+            super::lifetime::clean_stale_runs(&super::lifetime::runs_directory());
+        }
+
+        let (program, args) = launch_command(&path, args, options.launcher.as_deref());
+        let original_pid = wait_on_child(launch(&program, &args, options)?, event_tx.clone());
+        tracker.watch_child(Pid::from_u32(original_pid));
+
+        // The process leads its own group so this records everything it starts.
+        #[cfg(target_os = "linux")]
+        let run_record = if options.die_with_parent {
+            super::lifetime::RunRecord::create(&super::lifetime::runs_directory(), original_pid)
+                .map_err(|e| warn!("Failed to record the run for cleanup: {}", e))
+                .ok()
+        } else {
+            None
+        };
+
+        // The launcher isn't LabVIEW so we have to find the real process once it starts.
+        if options.launcher.is_some() {
+            tracker.watch_launcher(Pid::from_u32(original_pid));
+        }
+
+        let termination = options.termination;
+        let exited = Arc::new(AtomicBool::new(false));
+        let thread_exited = exited.clone();
 
         let monitor_thread = std::thread::Builder::new()
             .name("Process Monitor".to_string())
             .spawn(move || {
                 let mut current_pid = Some(Pid::from_u32(original_pid));
+                let mut action_channel = Some(action_channel);
 
                 // Loop until we recieve a stop. The only way to leave is when the main thread has sent stop.
                 // if we stop independently we get a race condition where the main loop will send stop to an invalid channel.
                 // Wrap the PID in the option where None means we have lost the process to gate on the kill process.
                 loop {
-                    match stop_rx.try_recv() {
-                        Ok(kill) => {
+                    if app_stop.load(Ordering::Relaxed) {
+                        action_channel = None;
+                    }
+
+                    match event_rx.recv_timeout(POLL_INTERVAL) {
+                        Ok(MonitorEvent::Stop(kill, termination_override)) => {
                             //stop requested. See if we have been asked to kill the process.
                             //disable if we aren't tracking a process though.
                             if let Some(pid) = current_pid {
-                                kill_process_with_timeout(kill, &thread_path, pid)
+                                tracker.sample_usage(pid);
+                                kill_process_with_timeout(
+                                    kill,
+                                    termination_override.unwrap_or(termination),
+                                    &mut tracker,
+                                    pid,
+                                    &event_rx,
+                                )
                             };
                             debug!(
                                 "Stopping LabVIEW monitoring due to stop command from application"
                             );
                             break;
                         }
-
-                        Err(_) => {
-                            //no stop command. Validate processes if we are still monitoring a pid.
+                        Ok(MonitorEvent::Cancel(grace)) => {
                             if let Some(pid) = current_pid {
-                                if let Some(id) = check_process(&thread_path, pid) {
-                                    current_pid = Some(id);
-                                } else {
-                                    debug!("The LabVIEW process appears to have closed down");
-                                    current_pid = None;
-                                }
+                                tracker.sample_usage(pid);
+                                cancel(&mut tracker, pid, grace, termination, &event_rx);
+                            };
+                            debug!("Stopping LabVIEW monitoring after cancelling the run");
+                            break;
+                        }
+                        Ok(MonitorEvent::ChildExited(usage)) => {
+                            tracker.set_child_exited(usage);
+                            current_pid = update_process(&mut tracker, current_pid);
+                        }
+                        Ok(MonitorEvent::Connected) => {
+                            tracker.set_connected();
+                            if let Some(pid) = current_pid {
+                                tracker.log_connected(pid);
                             }
                         }
+                        Err(RecvTimeoutError::Timeout) => {
+                            //no stop command. Validate processes if we are still monitoring a pid.
+                            current_pid = update_process(&mut tracker, current_pid);
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            debug!("Monitoring handle dropped without a stop command");
+                            break;
+                        }
                     }
 
-                    sleep(POLL_INTERVAL);
+                    if current_pid.is_none() && !thread_exited.swap(true, Ordering::Relaxed) {
+                        if let Some(channel) = action_channel.take() {
+                            // The action loop may not be running yet so this can fail.
+                            let _ = channel.send(ActionMessage::ProcessExited);
+                        }
+                    }
                 }
                 debug!("Monitoring thread completed");
+                tracker.stats.stats()
             })
             .expect("Could not create monitor thread.");
 
         Ok(Self {
-            stop_channel: stop_tx,
+            stop_channel: event_tx,
             port_registration,
+            preferences,
+            #[cfg(target_os = "linux")]
+            _run_record: run_record,
             monitor_thread,
+            exited,
         })
     }
 
+    /// Returns true once the process has been lost.
+    pub fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Relaxed)
+    }
+
+    /// Waits up to `timeout` for the process to be lost. Returns true if it has.
+    pub fn wait_for_exit(&self, timeout: Duration) -> bool {
+        let end_time = Instant::now() + timeout;
+        while !self.has_exited() && Instant::now() < end_time {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        self.has_exited()
+    }
+
     /// Send a stop command to the monitoring thread and blocks until complete.
+    /// Returns the resource usage of the process over the run.
     ///
     /// * `kill_process` - Set to None to leave the process running or provide a timeout for when the process should be killed if it is still active.
-    pub fn stop(self, kill_process: Option<Duration>) {
+    pub fn stop(self, kill_process: Option<Duration>) -> ProcessStats {
+        self.send_stop(kill_process, None)
+    }
+
+    /// Stops monitoring and asks the process to terminate, killing it if it is still running after the grace period.
+    /// On Windows the process is killed straight away.
+    pub fn stop_gracefully(self, grace: Duration) -> ProcessStats {
+        self.send_stop(
+            Some(Duration::from_millis(1)),
+            Some(TerminationPolicy::Graceful(grace)),
+        )
+    }
+
+    /// Stops monitoring and asks the process to terminate.
+    /// If it is still running after the grace period it is killed according to the termination policy.
+    pub fn cancel(self, grace: Duration) -> ProcessStats {
+        self.send_event(MonitorEvent::Cancel(grace))
+    }
+
+    fn send_stop(
+        self,
+        kill_process: Option<Duration>,
+        termination: Option<TerminationPolicy>,
+    ) -> ProcessStats {
+        self.send_event(MonitorEvent::Stop(kill_process, termination))
+    }
+
+    fn send_event(self, event: MonitorEvent) -> ProcessStats {
         //todo: error handling
-        self.stop_channel.send(kill_process).unwrap();
+        self.stop_channel.send(event).unwrap();
         debug!("Waiting on monitoring thread to complete");
-        self.monitor_thread.join().unwrap();
+        self.monitor_thread.join().unwrap()
+    }
+
+    /// Stops monitoring and leaves the process running along with the preference file it was started with.
+    pub fn detach(mut self) -> ProcessStats {
+        if let Some(preferences) = self.preferences.take() {
+            preferences.keep();
+        }
+        self.stop(None)
+    }
+
+    /// Kills the process and removes its service locator registration so the launch can be tried again.
+    pub fn abandon(mut self) -> ProcessStats {
+        if let Some(registration) = self.port_registration.take() {
+            if let Err(e) = registration.unregister() {
+                warn!("Failed to remove the service locator registration: {}", e);
+            }
+        }
+        self.stop(Some(Duration::from_millis(1)))
     }
 
     /// Registers that the comms are connected so any action required can be taken like cancelling service discovery.
@@ -96,6 +380,10 @@ impl MonitoredProcess {
         // We will consume the registration so take it out of the monitor.
         let port_registration = self.port_registration.take();
 
+        // Let the monitor record which process we are connected to.
+        // It only stops after we send stop so this can't fail.
+        let _ = self.stop_channel.send(MonitorEvent::Connected);
+
         if let Some(registration) = port_registration {
             registration.unregister()?;
         }
@@ -104,73 +392,762 @@ impl MonitoredProcess {
     }
 }
 
+/// Check the process we are monitoring, if any, and return the PID we should monitor from now on.
+fn update_process(tracker: &mut ProcessTracker, current_pid: Option<Pid>) -> Option<Pid> {
+    let pid = current_pid?;
+    let new_pid = tracker.check_process(pid);
+    if new_pid.is_none() {
+        debug!("The LabVIEW process appears to have closed down");
+    }
+    new_pid
+}
+
 /// If the kill option is set then it will give the process that duration to stop on it's own.
-/// After that timeout, it will use the kill command.
-fn kill_process_with_timeout(kill_option: Option<Duration>, thread_path: &Path, pid: Pid) {
+/// After that timeout, it will terminate it according to the termination policy.
+fn kill_process_with_timeout(
+    kill_option: Option<Duration>,
+    termination: TerminationPolicy,
+    tracker: &mut ProcessTracker,
+    pid: Pid,
+    events: &Receiver<MonitorEvent>,
+) {
     if let Some(timeout) = kill_option {
         info!(
             "Forcing LabVIEW to terminate in {}ms if it doesn't close.",
             timeout.as_millis()
         );
-        let end_time = Instant::now() + timeout;
 
-        loop {
-            let process_closed = check_process(thread_path, pid).is_none();
-            let timeout_passed = Instant::now() > end_time;
-            if process_closed {
-                break;
-            } else if timeout_passed {
-                //kill the process.
-                kill(pid);
-                break;
-            } else {
-                sleep(POLL_INTERVAL);
-            }
+        if let Some(pid) = wait_for_close(tracker, pid, timeout, events) {
+            terminate(tracker, pid, termination, events);
         }
     } else {
         debug!("Monitoring complete and kill not requested.");
     }
 }
 
-/// Checks if the process is still running and returns the new PID if it is.
-fn check_process(thread_path: &Path, current_pid: Pid) -> Option<Pid> {
-    let matching_processes = find_instances(thread_path);
-    let process_result = find_process(&matching_processes, current_pid);
-    if let Some(id) = process_result {
-        if id != current_pid {
-            info!("Process lost + found at PID {}", id);
+/// Ask the process to terminate and give it the grace period before terminating it according to the policy.
+/// Windows can't ask so it goes straight to the policy.
+fn cancel(
+    tracker: &mut ProcessTracker,
+    pid: Pid,
+    #[cfg_attr(target_os = "windows", allow(unused_variables))] grace: Duration,
+    termination: TerminationPolicy,
+    events: &Receiver<MonitorEvent>,
+) {
+    #[cfg(not(target_os = "windows"))]
+    {
+        info!(
+            "Cancelling LabVIEW. It has {}ms to close.",
+            grace.as_millis()
+        );
+        tracker.signal(pid, Signal::Term);
+        kill_process_with_timeout(Some(grace), termination, tracker, pid, events);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        debug!("Cancelling is not supported on Windows. Terminating instead.");
+        terminate(tracker, pid, termination, events);
+    }
+}
+
+/// Terminate the process according to the policy.
+fn terminate(
+    tracker: &mut ProcessTracker,
+    pid: Pid,
+    termination: TerminationPolicy,
+    #[cfg_attr(target_os = "windows", allow(unused_variables))] events: &Receiver<MonitorEvent>,
+) {
+    match termination {
+        #[cfg(not(target_os = "windows"))]
+        TerminationPolicy::Graceful(grace) => {
+            info!(
+                "Asking LabVIEW to terminate. It will be killed in {}ms if it doesn't close.",
+                grace.as_millis()
+            );
+            tracker.signal(pid, Signal::Term);
+            if let Some(pid) = wait_for_close(tracker, pid, grace, events) {
+                tracker.signal(pid, Signal::Kill);
+            }
         }
-    } else {
-        info!("Process Lost");
+        #[cfg(target_os = "windows")]
+        TerminationPolicy::Graceful(_) => {
+            debug!("Graceful termination is not supported on Windows. Killing instead.");
+            tracker.signal(pid, Signal::Kill);
+        }
+        TerminationPolicy::Immediate => tracker.signal(pid, Signal::Kill),
     }
-    process_result
 }
 
-/// Launches the LabVIEW process.
+/// Waits up to the timeout for the process to close.
+/// Returns the PID if it is still running.
+fn wait_for_close(
+    tracker: &mut ProcessTracker,
+    pid: Pid,
+    timeout: Duration,
+    events: &Receiver<MonitorEvent>,
+) -> Option<Pid> {
+    let end_time = Instant::now() + timeout;
+    let mut pid = pid;
+
+    loop {
+        pid = tracker.check_process(pid)?;
+
+        let now = Instant::now();
+        if now >= end_time {
+            return Some(pid);
+        }
+
+        // Wait for the next check but wake immediately if the child exits.
+        let wait = POLL_INTERVAL.min(end_time - now);
+        if let Ok(MonitorEvent::ChildExited(usage)) = events.recv_timeout(wait) {
+            tracker.set_child_exited(usage);
+        }
+    }
+}
+
+/// Tracks the state of the LabVIEW process.
+///
+/// This keeps a single system instance and only refreshes what it needs to
+/// so we don't have to scan every process on the machine each check.
+struct ProcessTracker {
+    path: PathBuf,
+    system: System,
+    /// The PID we launched if we will be notified when it exits.
+    watched_child: Option<Pid>,
+    child_exited: bool,
+    stats: StatsCollector,
+    /// Instances that were running before we launched. These aren't ours to kill.
+    existing: HashSet<Pid>,
+    kill_existing: bool,
+    /// The launcher PID until we have found the LabVIEW process it started.
+    launcher: Option<Pid>,
+    /// When we last scanned for the LabVIEW process started by the launcher.
+    last_launcher_scan: Option<Instant>,
+    /// Processes we have launched or followed. Any LabVIEW descended from these is ours.
+    lineage: HashSet<Pid>,
+    /// Once connected, LabVIEW has finished any handoff to another instance.
+    connected: bool,
+}
+
+impl ProcessTracker {
+    /// Create the tracker, recording any instances that are already running.
+    /// This must be called before we launch our process.
+    fn new(path: PathBuf, kill_existing: bool) -> Self {
+        let mut tracker = Self {
+            path,
+            system: System::new(),
+            watched_child: None,
+            child_exited: false,
+            stats: StatsCollector::new(),
+            existing: HashSet::new(),
+            kill_existing,
+            launcher: None,
+            last_launcher_scan: None,
+            lineage: HashSet::new(),
+            connected: false,
+        };
+
+        tracker.existing = tracker.find_instances().into_keys().collect();
+        if !tracker.existing.is_empty() {
+            info!(
+                "LabVIEW is already running with PID(s) {:?} before launch",
+                tracker.existing
+            );
+        }
+
+        tracker
+    }
+
+    /// Set the child we launched which we will be notified about when it exits.
+    fn watch_child(&mut self, pid: Pid) {
+        self.watched_child = Some(pid);
+        self.add_lineage(pid);
+    }
+
+    /// Record a process as part of this run.
+    fn add_lineage(&mut self, pid: Pid) {
+        self.lineage.insert(pid);
+    }
+
+    /// Record that the application has connected to us.
+    fn set_connected(&mut self) {
+        self.connected = true;
+    }
+
+    /// Set the launcher we started LabVIEW through.
+    fn watch_launcher(&mut self, pid: Pid) {
+        self.launcher = Some(pid);
+    }
+
+    /// Returns true if the process was started after we launched.
+    fn launched_by_us(&self, pid: Pid) -> bool {
+        !self.existing.contains(&pid)
+    }
+
+    /// Log which process is hosting the connection.
+    fn log_connected(&self, pid: Pid) {
+        if self.launched_by_us(pid) {
+            info!("Application connected from PID {} launched by G-CLI", pid);
+        } else {
+            info!(
+                "Application connected from PID {} which was already running before launch",
+                pid
+            );
+        }
+    }
+
+    /// Record that the child we launched has exited.
+    fn set_child_exited(&mut self, usage: Option<ResourceUsage>) {
+        self.child_exited = true;
+        // The usage of a launcher isn't the usage of LabVIEW.
+        let is_launcher = self.launcher.is_some() && self.launcher == self.watched_child;
+        if let Some(usage) = usage.filter(|_| !is_launcher) {
+            self.stats.record_usage(usage);
+        }
+    }
+
+    /// Take a final sample of the resource usage of a process that is still running.
+    fn sample_usage(&mut self, pid: Pid) {
+        // Once the child has exited we already have its usage.
+        if self.watched_child == Some(pid) && self.child_exited {
+            return;
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(usage) = read_proc_usage(pid) {
+            self.stats.record_usage(usage);
+            return;
+        }
+
+        #[cfg(target_os = "windows")]
+        if let Some(usage) = read_process_usage(pid) {
+            self.stats.record_usage(usage);
+            return;
+        }
+
+        if self
+            .system
+            .refresh_process_specifics(pid, ProcessRefreshKind::new())
+        {
+            if let Some(process) = self.system.process(pid) {
+                self.stats.sample_memory(process.memory());
+            }
+        }
+    }
+
+    /// Checks if the process is still running and returns the new PID if it is.
+    ///
+    /// Only if the process has gone do we scan the process table, in case LabVIEW has handed off to another PID.
+    fn check_process(&mut self, current_pid: Pid) -> Option<Pid> {
+        if self.launcher == Some(current_pid) {
+            return self.follow_launcher(current_pid);
+        }
+
+        if self.is_running(current_pid) {
+            return Some(current_pid);
+        }
+
+        // Only take over instances from our own run so we don't monitor or kill another run of LabVIEW.
+        let matching_processes = self.find_instances();
+        let lineages: HashMap<Pid, Lineage> = matching_processes
+            .keys()
+            .map(|pid| (*pid, self.lineage(*pid)))
+            .collect();
+        let with_lineage = |lineage: Lineage| -> HashMap<Pid, String> {
+            matching_processes
+                .iter()
+                .filter(|(pid, _)| lineages[pid] == lineage && self.launched_by_us(**pid))
+                .map(|(pid, name)| (*pid, name.clone()))
+                .collect()
+        };
+
+        let process_result = if let Some(id) =
+            find_process(&with_lineage(Lineage::Ours), current_pid)
+        {
+            info!("Process lost + found at PID {} started by this run", id);
+            Some(id)
+        } else if let Some(id) = find_process(&with_lineage(Lineage::Unknown), current_pid) {
+            info!(
+                "Process lost + found at PID {} by matching the install path as its lineage couldn't be determined",
+                id
+            );
+            Some(id)
+        } else if let Some(id) = self.handoff_target(&matching_processes) {
+            info!(
+                "Process lost + found at PID {} which was already running. LabVIEW has handed off to it",
+                id
+            );
+            Some(id)
+        } else {
+            info!("Process Lost");
+            self.stats.finish();
+            None
+        };
+
+        if let Some(id) = process_result {
+            self.add_lineage(id);
+        }
+        process_result
+    }
+
+    /// LabVIEW passes the VI to an instance of the same install that is already running and exits.
+    /// This can only happen before the application connects to us.
+    fn handoff_target(&self, instances: &HashMap<Pid, String>) -> Option<Pid> {
+        if self.connected {
+            return None;
+        }
+        instances
+            .keys()
+            .find(|pid| !self.launched_by_us(**pid))
+            .copied()
+    }
+
+    /// Works out if the process belongs to this run from its ancestors.
+    /// The processes must have been refreshed first.
+    fn lineage(&self, pid: Pid) -> Lineage {
+        // LabVIEW is reparented if its parent exits but stays in the process group we created.
+        #[cfg(not(target_os = "windows"))]
+        if let Some(group) = self.watched_child {
+            // Safety: getpgid has no memory safety requirements.
+            let pgid = unsafe { libc::getpgid(pid.as_u32() as libc::pid_t) };
+            if pgid == group.as_u32() as libc::pid_t {
+                return Lineage::Ours;
+            }
+        }
+
+        let parents = self
+            .system
+            .processes()
+            .iter()
+            .map(|(pid, process)| (*pid, process.parent()))
+            .collect();
+        process_lineage(&parents, &self.lineage, pid)
+    }
+
+    /// Looks for the LabVIEW process started through the launcher.
+    ///
+    /// Launchers like `nice` replace themselves with LabVIEW while others like `xvfb-run` start it as a child.
+    /// Until it is found we keep monitoring the launcher.
+    fn follow_launcher(&mut self, launcher: Pid) -> Option<Pid> {
+        // Scanning every process is expensive so only do it occasionally until the launcher exits.
+        let launcher_running = self.launcher_running(launcher);
+        let scanned_recently = self
+            .last_launcher_scan
+            .is_some_and(|scan| scan.elapsed() < LAUNCHER_SCAN_INTERVAL);
+        if launcher_running && scanned_recently {
+            return Some(launcher);
+        }
+        self.last_launcher_scan = Some(Instant::now());
+
+        let new_instances: Vec<Pid> = self
+            .find_instances()
+            .into_keys()
+            .filter(|pid| self.launched_by_us(*pid))
+            .collect();
+
+        let started = new_instances
+            .iter()
+            .find(|pid| self.lineage(**pid) == Lineage::Ours)
+            // If the launcher has gone we can't trace LabVIEW back to it.
+            // Never take an instance we can trace to somewhere else, like another run.
+            .or_else(|| {
+                let unknown = new_instances
+                    .iter()
+                    .find(|pid| !launcher_running && self.lineage(**pid) == Lineage::Unknown);
+                if let Some(pid) = unknown {
+                    warn!(
+                        "The launcher exited so LabVIEW at PID {} can't be traced to this run. Following it anyway",
+                        pid
+                    );
+                }
+                unknown
+            });
+
+        if let Some(&pid) = started {
+            info!("Following LabVIEW at PID {} started by the launcher", pid);
+            self.launcher = None;
+            self.add_lineage(pid);
+            Some(pid)
+        } else if launcher_running {
+            Some(launcher)
+        } else {
+            info!("Process Lost - the launcher exited without starting LabVIEW");
+            self.stats.finish();
+            None
+        }
+    }
+
+    fn launcher_running(&mut self, launcher: Pid) -> bool {
+        if self.watched_child == Some(launcher) {
+            !self.child_exited
+        } else {
+            self.system
+                .refresh_process_specifics(launcher, ProcessRefreshKind::new())
+        }
+    }
+
+    /// Check a single PID is still running LabVIEW.
+    fn is_running(&mut self, pid: Pid) -> bool {
+        if self.watched_child == Some(pid) {
+            return !self.child_exited;
+        }
+
+        // Check the path as well in case the PID has been reused.
+        let refreshed = self
+            .system
+            .refresh_process_specifics(pid, ProcessRefreshKind::new());
+        match self.system.process(pid) {
+            Some(process) if refreshed && process.exe().starts_with(&self.path) => {
+                self.stats.sample_memory(process.memory());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns a list of all instances running of LabVIEW
+    fn find_instances(&mut self) -> HashMap<Pid, String> {
+        self.system
+            .refresh_processes_specifics(ProcessRefreshKind::new());
+        let mut processes = HashMap::new();
+
+        for (pid, process) in self.system.processes() {
+            let process_path = process.exe();
+            // We need to compare starts_with as linux will add suffixes for license version.
+            if process_path.starts_with(&self.path) {
+                processes.insert(*pid, process.name().to_owned());
+            }
+        }
+
+        processes
+    }
+
+    /// Signal the process by PID along with any processes it has started.
+    ///
+    /// On Linux the child leads its own process group so we also signal the group,
+    /// which catches helpers that have been orphaned from the tree.
+    fn signal(&mut self, pid: Pid, signal: Signal) {
+        if !self.launched_by_us(pid) && !self.kill_existing {
+            warn!(
+                "Not killing LabVIEW process ({}) as it was already running before G-CLI launched. Use --kill-existing to allow this.",
+                pid
+            );
+            return;
+        }
+
+        info!(
+            "{} LabVIEW process ({}) and its children",
+            signal_verb(signal),
+            pid
+        );
+        self.system
+            .refresh_processes_specifics(ProcessRefreshKind::new());
+
+        // Once our child has exited the PID is free to be reused so we must not touch it.
+        let child_gone = self.watched_child == Some(pid) && self.child_exited;
+        let tree = if child_gone {
+            Vec::new()
+        } else {
+            let parents = self
+                .system
+                .processes()
+                .iter()
+                .map(|(pid, process)| (*pid, process.parent()))
+                .collect();
+            process_tree(&parents, pid)
+        };
+        let group = self.signal_process_group(signal);
+
+        if tree.is_empty() && group.is_empty() {
+            info!("Process ID Not Found to kill. It may have already closed.");
+        }
+
+        for pid in tree.into_iter().filter(|pid| !group.contains(pid)) {
+            self.log_reaped(pid, signal);
+            if let Some(process) = self.system.process(pid) {
+                process.kill_with(signal);
+            }
+        }
+    }
+
+    /// Signals the process group of the child we launched and returns the processes that were in it.
+    /// There are no process groups on Windows so nothing is signalled.
+    #[cfg_attr(target_os = "windows", allow(unused_variables))]
+    fn signal_process_group(&self, signal: Signal) -> Vec<Pid> {
+        #[cfg(not(target_os = "windows"))]
+        if let Some(group) = self.watched_child {
+            let members = self.process_group_members(group);
+            if !members.is_empty() {
+                for member in &members {
+                    self.log_reaped(*member, signal);
+                }
+                let c_signal = match signal {
+                    Signal::Term => libc::SIGTERM,
+                    _ => libc::SIGKILL,
+                };
+                // Safety: killpg has no memory safety requirements.
+                unsafe {
+                    libc::killpg(group.as_u32() as libc::pid_t, c_signal);
+                }
+            }
+            return members;
+        }
+        Vec::new()
+    }
+
+    fn log_reaped(&self, pid: Pid, signal: Signal) {
+        let verb = signal_verb(signal);
+        match self.system.process(pid) {
+            Some(process) => info!("{} process {} ({})", verb, process.name(), pid),
+            None => info!("{} process {}", verb, pid),
+        }
+    }
+
+    /// Finds all running processes in the process group.
+    #[cfg(not(target_os = "windows"))]
+    fn process_group_members(&self, group: Pid) -> Vec<Pid> {
+        self.system
+            .processes()
+            .keys()
+            .filter(|pid| {
+                // Safety: getpgid has no memory safety requirements.
+                let pgid = unsafe { libc::getpgid(pid.as_u32() as libc::pid_t) };
+                pgid == group.as_u32() as libc::pid_t
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// Describes the signal for logging.
+fn signal_verb(signal: Signal) -> &'static str {
+    match signal {
+        Signal::Term => "Terminating",
+        _ => "Killing",
+    }
+}
+
+/// Waits on the child in its own thread so the monitor is notified as soon as it exits.
 /// Returns the process ID.
 #[cfg(not(target_os = "windows"))]
-fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
+fn wait_on_child(child: std::process::Child, events: Sender<MonitorEvent>) -> u32 {
+    let pid = child.id();
+
+    std::thread::Builder::new()
+        .name("Process Waiter".to_string())
+        .spawn(move || {
+            let usage = reap_child(child);
+            // The monitor may have already stopped so this can fail.
+            let _ = events.send(MonitorEvent::ChildExited(usage));
+        })
+        .expect("Could not create process waiter thread.");
+
+    pid
+}
+
+/// Blocks until the child exits and returns its resource usage.
+///
+/// We use wait4 rather than [`std::process::Child::wait`] so the OS gives us the usage as we reap it.
+#[cfg(not(target_os = "windows"))]
+fn reap_child(child: std::process::Child) -> Option<ResourceUsage> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // Safety: rusage is plain data so all zeros is a valid value.
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    loop {
+        // Safety: the pointers are to valid locals for the duration of the call.
+        let result = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut rusage) };
+        if result != -1 {
+            break;
+        }
+
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            debug!("Failed to wait on launched process: {}", error);
+            return None;
+        }
+    }
+
+    debug!(
+        "Launched process exited with {}",
+        std::process::ExitStatus::from_raw(status)
+    );
+
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+
+    // Linux reports the max RSS in KiB but macOS uses bytes.
+    #[cfg(target_os = "macos")]
+    let peak_memory = rusage.ru_maxrss as u64;
+    #[cfg(not(target_os = "macos"))]
+    let peak_memory = rusage.ru_maxrss as u64 * 1024;
+
+    Some(ResourceUsage {
+        peak_memory,
+        cpu_time: to_duration(rusage.ru_utime) + to_duration(rusage.ru_stime),
+    })
+}
+
+/// Reads the usage of a running process from procfs.
+#[cfg(target_os = "linux")]
+fn read_proc_usage(pid: Pid) -> Option<ResourceUsage> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // Safety: sysconf has no memory safety requirements.
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_second <= 0 {
+        return None;
+    }
+
+    let (peak_memory, cpu_ticks) = parse_proc_usage(&status, &stat)?;
+
+    Some(ResourceUsage {
+        peak_memory,
+        cpu_time: Duration::from_secs_f64(cpu_ticks as f64 / ticks_per_second as f64),
+    })
+}
+
+/// Extracts the peak memory in bytes and CPU time in clock ticks from the procfs status and stat files.
+#[cfg(target_os = "linux")]
+fn parse_proc_usage(status: &str, stat: &str) -> Option<(u64, u64)> {
+    let peak_kib: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    // The name can contain spaces so start after the closing bracket.
+    // utime and stime are then the 12th and 13th fields.
+    let mut fields = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+
+    Some((peak_kib * 1024, utime + stime))
+}
+
+/// Returns the program and arguments to run, prefixed by the launcher if there is one.
+fn launch_command(
+    path: &Path,
+    args: &[OsString],
+    launcher: Option<&[OsString]>,
+) -> (PathBuf, Vec<OsString>) {
+    match launcher {
+        Some([program, launcher_args @ ..]) => {
+            let mut full_args = launcher_args.to_vec();
+            full_args.push(path.as_os_str().to_owned());
+            full_args.extend_from_slice(args);
+            (PathBuf::from(program), full_args)
+        }
+        _ => (path.to_owned(), args.to_vec()),
+    }
+}
+
+/// Launches the LabVIEW process.
+/// Returns the child process handle.
+#[cfg(not(target_os = "windows"))]
+fn launch(
+    path: &Path,
+    args: &[OsString],
+    options: &LaunchOptions,
+) -> Result<std::process::Child, LabVIEWError> {
+    use std::fs::File;
+    use std::os::unix::process::CommandExt;
     use std::process::{Command, Stdio};
-    //map stdin, out and err to null to prevent holding this process open.
+    //map stdin, out and err to null or a file to prevent holding this process open.
+
+    let (stdout, stderr) = match &options.output_log {
+        Some(log_path) => {
+            let log_error = |e| LabVIEWError::OutputLogFailed(e, log_path.clone());
+            let file = File::create(log_path).map_err(log_error)?;
+            let stdout = file.try_clone().map_err(log_error)?;
+            debug!("Capturing application output to {}", log_path.display());
+            (Stdio::from(stdout), Stdio::from(file))
+        }
+        None => (Stdio::null(), Stdio::null()),
+    };
 
     let mut command = Command::new(path);
     command
         .args(args)
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
+        .stdout(stdout)
+        .stderr(stderr);
+
+    // Lead a new session, and so a new process group, like DETACHED_PROCESS on Windows.
+    // Ctrl+C and terminal hangups then only reach G-CLI and we can kill everything LabVIEW starts.
+    // Safety: setsid is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    if let Some(dir) = &options.working_dir {
+        command.current_dir(dir);
+    }
+
+    if options.die_with_parent {
+        set_parent_death_signal(&mut command);
+    }
+
+    if !options.environment.is_inherited() {
+        command
+            .env_clear()
+            .envs(options.environment.resolve(std::env::vars_os()));
+    }
 
     let launch_result = command.spawn();
 
     match launch_result {
-        Ok(output) => {
-            debug!("Process launched with PID {}", output.id());
-            Ok(output.id())
+        Ok(child) => {
+            debug!("Process launched with PID {}", child.id());
+            Ok(child)
         }
         Err(e) => Err(LabVIEWError::ProcessLaunchFailed(e)),
     }
 }
 
+/// Has the kernel kill the process if G-CLI dies.
+///
+/// The signal is sent when the thread that spawned it exits, so this must be called from the main thread.
+#[cfg(target_os = "linux")]
+fn set_parent_death_signal(command: &mut std::process::Command) {
+    use std::os::unix::process::CommandExt;
+
+    let parent = std::process::id() as libc::pid_t;
+    // Safety: only async-signal-safe functions are called between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // We may have died before the signal was set up.
+            if libc::getppid() != parent {
+                return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(all(not(target_os = "linux"), not(target_os = "windows")))]
+fn set_parent_death_signal(_command: &mut std::process::Command) {
+    warn!("Tying the application to the lifetime of G-CLI is only supported on Linux.");
+}
+
 /// This module takes utility functions from the std library of rust
 /// that we lost by bypassing the standard process module.
 ///
@@ -263,11 +1240,130 @@ mod process_utilities {
     }
 }
 
-/// Launches the LabVIEW process.
+/// A process handle which is closed when dropped.
+#[cfg(target_os = "windows")]
+struct ProcessHandle(windows::Win32::Foundation::HANDLE);
+
+#[cfg(target_os = "windows")]
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        // Safety: we own the handle and nothing uses it after this.
+        unsafe {
+            let _ = windows::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+/// A process we launched on Windows.
+/// Holding the handle keeps its usage available after it exits.
+#[cfg(target_os = "windows")]
+struct ChildProcess {
+    pid: u32,
+    handle: ProcessHandle,
+}
+
+/// Waits on the child in its own thread so the monitor is notified as soon as it exits.
 /// Returns the process ID.
+#[cfg(target_os = "windows")]
+fn wait_on_child(child: ChildProcess, events: Sender<MonitorEvent>) -> u32 {
+    let pid = child.pid;
+
+    std::thread::Builder::new()
+        .name("Process Waiter".to_string())
+        .spawn(move || {
+            let usage = reap_child(child);
+            // The monitor may have already stopped so this can fail.
+            let _ = events.send(MonitorEvent::ChildExited(usage));
+        })
+        .expect("Could not create process waiter thread.");
+
+    pid
+}
+
+/// Blocks until the child exits and returns its resource usage.
+#[cfg(target_os = "windows")]
+fn reap_child(child: ChildProcess) -> Option<ResourceUsage> {
+    use windows::Win32::Foundation::WAIT_OBJECT_0;
+    use windows::Win32::System::Threading::{WaitForSingleObject, INFINITE};
+
+    // Safety: the handle is valid until the child is dropped.
+    if unsafe { WaitForSingleObject(child.handle.0, INFINITE) } != WAIT_OBJECT_0 {
+        debug!(
+            "Failed to wait on launched process: {}",
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+    debug!("Launched process exited");
+
+    process_usage(&child.handle)
+}
+
+/// Reads the usage of a running process by opening a handle to it.
+#[cfg(target_os = "windows")]
+fn read_process_usage(pid: Pid) -> Option<ResourceUsage> {
+    use windows::Win32::System::Threading::{
+        OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_VM_READ,
+    };
+
+    // Safety: OpenProcess has no memory safety requirements.
+    let handle = unsafe {
+        OpenProcess(
+            PROCESS_QUERY_LIMITED_INFORMATION | PROCESS_VM_READ,
+            false,
+            pid.as_u32(),
+        )
+    }
+    .ok()?;
+    process_usage(&ProcessHandle(handle))
+}
+
+/// Gets the CPU time and peak working set of the process, which stay available after it has exited.
+#[cfg(target_os = "windows")]
+fn process_usage(handle: &ProcessHandle) -> Option<ResourceUsage> {
+    use windows::Win32::Foundation::FILETIME;
+    use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::GetProcessTimes;
+
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    let mut memory = PROCESS_MEMORY_COUNTERS::default();
+
+    // Safety: the pointers are to valid locals for the duration of the calls.
+    unsafe {
+        GetProcessTimes(handle.0, &mut creation, &mut exit, &mut kernel, &mut user).ok()?;
+        GetProcessMemoryInfo(
+            handle.0,
+            &mut memory,
+            std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        )
+        .ok()?;
+    }
+
+    // Process times are counted in 100ns intervals.
+    let to_duration = |time: FILETIME| {
+        let intervals = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        Duration::from_nanos(intervals * 100)
+    };
+
+    Some(ResourceUsage {
+        peak_memory: memory.PeakWorkingSetSize as u64,
+        cpu_time: to_duration(kernel) + to_duration(user),
+    })
+}
+
+/// Launches the LabVIEW process.
+/// Returns the process, keeping its handle so we can wait on it and read its usage.
 /// This is a specialised version using the windows API to avoid handle inheritance.
 #[cfg(target_os = "windows")]
-fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
+fn launch(
+    path: &Path,
+    args: &[OsString],
+    options: &LaunchOptions,
+) -> Result<ChildProcess, LabVIEWError> {
+    use log::warn;
     use std::ptr;
     use windows::core::{PCWSTR, PWSTR};
     use windows::Win32::{
@@ -278,12 +1374,34 @@ fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
         },
     };
 
+    if options.output_log.is_some() {
+        warn!("Capturing the application output is not supported on Windows.");
+    }
+
+    if options.die_with_parent {
+        warn!("Tying the application to the lifetime of G-CLI is only supported on Linux.");
+    }
+
     let mut pi = PROCESS_INFORMATION::default();
     let si = STARTUPINFOW::default();
 
     //build out required command line.
     let mut command = process_utilities::make_command_line(path, args)?;
 
+    // None inherits our environment.
+    let environment = if options.environment.is_inherited() {
+        None
+    } else {
+        let environment = options.environment.resolve(std::env::vars_os());
+        Some(super::environment::environment_block(&environment)?)
+    };
+
+    // Null terminated working directory. A null pointer inherits ours.
+    let working_dir: Option<Vec<u16>> = options.working_dir.as_ref().map(|dir| {
+        use std::os::windows::ffi::OsStrExt;
+        dir.as_os_str().encode_wide().chain(Some(0)).collect()
+    });
+
     //app name not required - build it into command line.
     let dwcreationflags = CREATE_UNICODE_ENVIRONMENT | DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP;
     let success = unsafe {
@@ -294,8 +1412,12 @@ fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
             None,
             false,
             dwcreationflags,
-            None,
-            PCWSTR(ptr::null()),
+            environment
+                .as_ref()
+                .map(|block| block.as_ptr() as *const std::ffi::c_void),
+            working_dir
+                .as_ref()
+                .map_or(PCWSTR(ptr::null()), |dir| PCWSTR(dir.as_ptr())),
             &si,
             &mut pi as *mut PROCESS_INFORMATION,
         )
@@ -304,11 +1426,13 @@ fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
     if success.is_ok() {
         let pid = pi.dwProcessId;
         unsafe {
-            let _ = CloseHandle(pi.hProcess);
             let _ = CloseHandle(pi.hThread);
         }
         debug!("Process launched with PID {}", pid);
-        Ok(pid)
+        Ok(ChildProcess {
+            pid,
+            handle: ProcessHandle(pi.hProcess),
+        })
     } else {
         Err(LabVIEWError::ProcessLaunchFailed(
             std::io::Error::last_os_error(),
@@ -316,31 +1440,54 @@ fn launch(path: &Path, args: &[OsString]) -> Result<u32, LabVIEWError> {
     }
 }
 
-/// Returns a list of all instances running of LabVIEW
-fn find_instances(path: &Path) -> HashMap<Pid, String> {
-    let sys = System::new_all();
-    let mut processes = HashMap::new();
+/// Returns the root process and all of its descendants.
+/// The parents are provided as a map of PID to parent PID.
+fn process_tree(parents: &HashMap<Pid, Option<Pid>>, root: Pid) -> Vec<Pid> {
+    if !parents.contains_key(&root) {
+        return Vec::new();
+    }
 
-    for (pid, process) in sys.processes() {
-        let process_path = process.exe();
-        // We need to compare starts_with as linux will add suffixes for license version.
-        if process_path.starts_with(path) {
-            processes.insert(*pid, process.name().to_owned());
-        }
+    let mut tree = vec![root];
+    let mut index = 0;
+    while let Some(&parent) = tree.get(index) {
+        let children: Vec<Pid> = parents
+            .iter()
+            .filter(|(pid, ppid)| **ppid == Some(parent) && !tree.contains(pid))
+            .map(|(pid, _)| *pid)
+            .collect();
+        tree.extend(children);
+        index += 1;
     }
+    tree
+}
 
-    processes
+/// Whether a process belongs to this run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Lineage {
+    /// The process or one of its ancestors is part of the run.
+    Ours,
+    /// We followed its ancestors to the root without finding the run.
+    NotOurs,
+    /// An ancestor has exited so we can't tell.
+    Unknown,
 }
 
-/// Kill the process by PID
-fn kill(pid: Pid) {
-    info!("Killing LabVIEW process ({})", pid);
-    let sys = System::new_all();
-    if let Some(process) = sys.process(pid) {
-        process.kill();
-    } else {
-        info!("Process ID Not Found to kill. It may have already closed.");
+/// Follows the parents of the process to see if it descends from the run.
+/// The parents are provided as a map of PID to parent PID.
+fn process_lineage(parents: &HashMap<Pid, Option<Pid>>, ours: &HashSet<Pid>, pid: Pid) -> Lineage {
+    let mut current = pid;
+    // Bounded in case of a cycle from PID reuse.
+    for _ in 0..parents.len() + 1 {
+        if ours.contains(&current) {
+            return Lineage::Ours;
+        }
+        match parents.get(&current) {
+            Some(Some(parent)) if parent.as_u32() != 0 => current = *parent,
+            Some(_) => return Lineage::NotOurs,
+            None => return Lineage::Unknown,
+        }
     }
+    Lineage::Unknown
 }
 
 /// Find the process in the list.
@@ -389,4 +1536,165 @@ mod test {
 
         assert_eq!(find_process(&processes, Pid::from(1)), None);
     }
+
+    #[test]
+    fn process_tree_includes_descendants() {
+        let mut parents = HashMap::new();
+        parents.insert(Pid::from(1), None);
+        parents.insert(Pid::from(10), Some(Pid::from(1)));
+        parents.insert(Pid::from(11), Some(Pid::from(10)));
+        parents.insert(Pid::from(12), Some(Pid::from(11)));
+        parents.insert(Pid::from(20), Some(Pid::from(1)));
+
+        let mut tree = process_tree(&parents, Pid::from(10));
+        tree.sort();
+
+        assert_eq!(tree, vec![Pid::from(10), Pid::from(11), Pid::from(12)]);
+    }
+
+    #[test]
+    fn process_tree_missing_root() {
+        let mut parents = HashMap::new();
+        parents.insert(Pid::from(10), Some(Pid::from(1)));
+
+        assert_eq!(process_tree(&parents, Pid::from(1)), Vec::<Pid>::new());
+    }
+
+    fn test_parents() -> HashMap<Pid, Option<Pid>> {
+        let mut parents = HashMap::new();
+        parents.insert(Pid::from(1), None);
+        parents.insert(Pid::from(10), Some(Pid::from(1)));
+        parents.insert(Pid::from(11), Some(Pid::from(10)));
+        parents.insert(Pid::from(20), Some(Pid::from(1)));
+        // Parent 30 has exited.
+        parents.insert(Pid::from(31), Some(Pid::from(30)));
+        parents
+    }
+
+    #[test]
+    fn process_lineage_descendant_is_ours() {
+        let ours = HashSet::from([Pid::from(10)]);
+
+        assert_eq!(
+            process_lineage(&test_parents(), &ours, Pid::from(11)),
+            Lineage::Ours
+        );
+        assert_eq!(
+            process_lineage(&test_parents(), &ours, Pid::from(10)),
+            Lineage::Ours
+        );
+    }
+
+    #[test]
+    fn process_lineage_other_run_is_not_ours() {
+        let ours = HashSet::from([Pid::from(10)]);
+
+        assert_eq!(
+            process_lineage(&test_parents(), &ours, Pid::from(20)),
+            Lineage::NotOurs
+        );
+    }
+
+    #[test]
+    fn process_lineage_exited_parent_is_unknown() {
+        let ours = HashSet::from([Pid::from(10)]);
+
+        assert_eq!(
+            process_lineage(&test_parents(), &ours, Pid::from(31)),
+            Lineage::Unknown
+        );
+    }
+
+    #[test]
+    fn process_lineage_exited_parent_from_run_is_ours() {
+        // Windows keeps the parent PID after the parent has exited.
+        let ours = HashSet::from([Pid::from(30)]);
+
+        assert_eq!(
+            process_lineage(&test_parents(), &ours, Pid::from(31)),
+            Lineage::Ours
+        );
+    }
+
+    #[test]
+    fn launch_command_without_launcher() {
+        let (program, args) = launch_command(Path::new("app.exe"), &[OsString::from("-a")], None);
+
+        assert_eq!(program, PathBuf::from("app.exe"));
+        assert_eq!(args, vec![OsString::from("-a")]);
+    }
+
+    #[test]
+    fn launch_command_with_launcher() {
+        let launcher = [OsString::from("nice"), OsString::from("-n10")];
+        let (program, args) = launch_command(
+            Path::new("labview"),
+            &[OsString::from("-a")],
+            Some(&launcher),
+        );
+
+        assert_eq!(program, PathBuf::from("nice"));
+        assert_eq!(
+            args,
+            vec![
+                OsString::from("-n10"),
+                OsString::from("labview"),
+                OsString::from("-a")
+            ]
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn child_leads_new_session() {
+        let mut child = launch(
+            Path::new("sleep"),
+            &[OsString::from("1000")],
+            &LaunchOptions::default(),
+        )
+        .unwrap();
+        let pid = child.id() as libc::pid_t;
+
+        // Safety: these have no memory safety requirements.
+        let (session, group) = unsafe { (libc::getsid(pid), libc::getpgid(pid)) };
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(session, pid);
+        assert_eq!(group, pid);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn child_exit_is_notified() {
+        let child = std::process::Command::new("true").spawn().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        wait_on_child(child, tx);
+
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(MonitorEvent::ChildExited(Some(_)))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_usage_from_proc() {
+        let status =
+            "Name:\tlabview\nVmPeak:\t  300000 kB\nVmHWM:\t  204800 kB\nVmRSS:\t  102400 kB\n";
+        let stat = "1234 (lab view) S 1 1234 1234 0 -1 4194560 500 0 0 0 150 50 0 0 20 0 1 0";
+
+        assert_eq!(parse_proc_usage(status, stat), Some((204800 * 1024, 200)));
+    }
+
+    #[test]
+    fn stats_keep_peak_memory() {
+        let mut collector = StatsCollector::new();
+        collector.sample_memory(100);
+        collector.sample_memory(300);
+        collector.sample_memory(200);
+
+        assert_eq!(collector.stats().peak_memory, Some(300));
+    }
 }
@@ -0,0 +1,140 @@
+//! Runs a private Xvfb server so LabVIEW can start on headless Linux machines.
+//!
+#[cfg(unix)]
+use log::debug;
+use log::{info, warn};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use super::error::LabVIEWError;
+
+/// The program we start for the display.
+const XVFB: &str = "Xvfb";
+
+/// How long we give Xvfb to report its display before we kill it.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long we give Xvfb to exit after asking it to before we kill it.
+#[cfg(unix)]
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Returns true if the launched process will need a display we have to provide.
+pub fn display_required() -> bool {
+    cfg!(target_os = "linux") && std::env::var_os("DISPLAY").is_none_or(|d| d.is_empty())
+}
+
+/// An Xvfb server owned by this run. It is shut down when dropped.
+#[derive(Debug)]
+pub struct VirtualDisplay {
+    server: Child,
+    display: String,
+}
+
+impl VirtualDisplay {
+    /// Starts Xvfb and waits for it to report the display it is serving.
+    pub fn start() -> Result<Self, LabVIEWError> {
+        // -displayfd makes Xvfb pick a free display and write the number to stdout once it is ready.
+        let mut command = Command::new(XVFB);
+        command
+            .args(["-displayfd", "1", "-nolisten", "tcp", "-screen", "0"])
+            .arg("1280x1024x24")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        // Like LabVIEW, keep it out of the terminal's process group so Ctrl+C and hangups only reach G-CLI.
+        // We shut it down once LabVIEW has been dealt with.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        let mut server = command.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => LabVIEWError::XvfbNotFound,
+            _ => LabVIEWError::VirtualDisplayFailed(e.to_string()),
+        })?;
+
+        // Read on another thread so a server that never reports a display can't hang the run.
+        let stdout = server.stdout.take().expect("Xvfb stdout is piped");
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("Xvfb Display Reader".to_string())
+            .spawn(move || {
+                let mut line = String::new();
+                let read = BufReader::new(stdout).read_line(&mut line);
+                let _ = tx.send(read.map(|_| line));
+            })
+            .expect("Could not create Xvfb reader thread.");
+
+        let failure = match rx.recv_timeout(STARTUP_TIMEOUT) {
+            Ok(Ok(line)) if !line.trim().is_empty() => {
+                let display = format!(":{}", line.trim());
+                info!("Started Xvfb on display {}", display);
+                return Ok(Self { server, display });
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                format!("Xvfb didn't report a display within {STARTUP_TIMEOUT:?}")
+            }
+            _ => "Xvfb exited before reporting a display".to_string(),
+        };
+
+        let _ = server.kill();
+        let status = server.wait();
+        Err(LabVIEWError::VirtualDisplayFailed(format!(
+            "{} ({})",
+            failure,
+            status.map_or_else(|e| e.to_string(), |s| s.to_string())
+        )))
+    }
+
+    /// The environment variables which point the process at this display.
+    pub fn environment(&self) -> Vec<(OsString, OsString)> {
+        vec![(OsString::from("DISPLAY"), OsString::from(&self.display))]
+    }
+
+    /// Leaves Xvfb running for an application which outlives us.
+    pub fn keep(self) {
+        info!(
+            "Leaving Xvfb running on display {} (PID {})",
+            self.display,
+            self.server.id()
+        );
+        std::mem::forget(self);
+    }
+
+    /// Asks Xvfb to exit so it can clean up its lock files, killing it if it doesn't.
+    fn shutdown(&mut self) {
+        #[cfg(unix)]
+        {
+            use std::time::Instant;
+
+            // Safety: kill has no memory safety requirements.
+            unsafe {
+                libc::kill(self.server.id() as libc::pid_t, libc::SIGTERM);
+            }
+            let end_time = Instant::now() + SHUTDOWN_TIMEOUT;
+            while Instant::now() < end_time {
+                if let Ok(Some(_)) = self.server.try_wait() {
+                    debug!("Xvfb on display {} has shut down", self.display);
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        warn!(
+            "Xvfb on display {} didn't shut down so killing it",
+            self.display
+        );
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+impl Drop for VirtualDisplay {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
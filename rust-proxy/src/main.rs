@@ -2,32 +2,56 @@ mod action_loop;
 mod cli;
 mod comms;
 mod comms_loop;
+mod error_report;
+mod exit_codes;
 mod labview;
 mod os_string_support;
 mod signal_loop;
+mod timeout_loop;
 
-use comms::{AppListener, MessageToLV};
+use comms::{AppListener, CommsError, MessageToLV};
 use eyre::{eyre, Report, Result, WrapErr};
-use labview::{detect_installations, installs::Bitness, launch_exe, launch_lv};
-use log::{debug, error, LevelFilter};
+use labview::{
+    detect_installations,
+    environment::EnvironmentOptions,
+    error::LabVIEWError,
+    installs::Bitness,
+    isolation::IsolatedRun,
+    launch_exe, launch_lv,
+    preferences::PreferenceOptions,
+    process::{LaunchOptions, MonitoredProcess, ProcessStats},
+    virtual_display::{self, VirtualDisplay},
+};
+use log::{debug, error, warn, LevelFilter};
 use simplelog::{format_description, ColorChoice, ConfigBuilder, TermLogger, TerminalMode};
 use std::time::Duration;
 
 use os_string_support::join_os_string;
 
 use crate::action_loop::{ActionLoop, ExitAction};
+use crate::error_report::ErrorFormat;
+use crate::exit_codes::{ExitCodeMap, ProxyFailure};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn main() -> Result<(), Report> {
+fn main() {
+    let config = cli::Configuration::from_env();
+
     //wrap the app seperately so destructors are all called
     //before exit.
-    let return_code = gcli()?;
+    let return_code = match gcli(&config) {
+        Ok(code) => code,
+        Err(report) => {
+            let failure = ProxyFailure::classify(&report);
+            let code = config.exit_codes.code(failure);
+            error_report::print(&config, &report, failure, code, None);
+            code
+        }
+    };
     std::process::exit(return_code);
 }
 
-fn gcli() -> Result<i32> {
-    let config = cli::Configuration::from_env();
+fn gcli(config: &cli::Configuration) -> Result<i32> {
     let program_args = cli::program_arguments(std::env::args_os());
     let cwd = std::env::current_dir().unwrap();
 
@@ -53,13 +77,116 @@ fn gcli() -> Result<i32> {
         error!("No launch was deprecated for v3.0.0")
     }
 
+    let exit_code_map = exit_code_map(config)?;
+
+    // Installed before anything is created so a signal during launch still stops LabVIEW and cleans up the run.
+    let mut signals = signal_loop::install(config.signal_actions)?;
+
     let app_listener = AppListener::new().wrap_err("Failed to create the network listener")?;
-    let mut process =
-        launch_process(&config, &app_listener).wrap_err("Failed to launch the process.")?;
 
-    let mut connection = app_listener
-        .wait_on_app(config.connect_timeout)
-        .wrap_err("No connection established with application.")?;
+    // Held until the end of the run so the directories are removed after the process is stopped.
+    let mut isolation = if config.isolate {
+        Some(
+            IsolatedRun::create(app_listener.port())
+                .wrap_err("Failed to create the isolated run directories")?,
+        )
+    } else {
+        None
+    };
+
+    let mut display = start_virtual_display(config)?;
+
+    // Launch and connect are retried until the app has connected. Once it has the arguments we never retry.
+    let mut attempt = 1;
+    let (action_loop, mut process, mut connection) = loop {
+        if let Some((signal, _)) = signals.try_next() {
+            debug!(
+                "Recieved signal {} before launching the application",
+                signal
+            );
+            return Ok(report_failure(
+                config,
+                ProxyFailure::Signal(signal),
+                format!("Recieved signal {signal} before launching the application"),
+                None,
+            ));
+        }
+
+        // The action loop is created before launch so the process monitor can report to it.
+        // Each attempt gets its own so messages about the process of a failed attempt are dropped.
+        let action_loop = ActionLoop::new(config.idle_timeout);
+
+        let launched = launch_process(
+            config,
+            isolation.as_ref(),
+            display.as_ref(),
+            &app_listener,
+            &action_loop,
+        )
+        .wrap_err("Failed to launch the process.");
+
+        let (error, process) = match launched {
+            Ok(process) => {
+                let mut signal = None;
+                let connected = app_listener.wait_on_app(config.connect_timeout, || {
+                    signal = signals.try_next();
+                    signal.is_some() || process.has_exited()
+                });
+                if let Some((signal, action)) = signal {
+                    debug!(
+                        "Recieved signal {} before the application connected",
+                        signal
+                    );
+                    let exit = ExitAction::for_signal(signal, action);
+                    keep_for_detached(&exit, config, &mut isolation, &mut display);
+                    return Ok(stop_for_exit(exit, process, &exit_code_map, config));
+                }
+                match connected {
+                    Ok(connection) => break (action_loop, process, connection),
+                    Err(e) => (e.into(), Some(process)),
+                }
+            }
+            Err(e) => (e, None),
+        };
+
+        let failure = ProxyFailure::classify(&error);
+        if attempt <= config.retries && is_retryable(&error) {
+            let delay = retry_delay(config.retry_delay, attempt);
+            warn!(
+                "Attempt {} of {} failed: {}. Retrying in {:?}",
+                attempt,
+                config.retries + 1,
+                error.root_cause(),
+                delay
+            );
+            if let Some(process) = process {
+                print_app_output(config);
+                process.abandon();
+            }
+            std::thread::sleep(delay);
+            attempt += 1;
+            continue;
+        }
+
+        match process {
+            Some(process) if failure == ProxyFailure::AppTerminated => {
+                error!("The application terminated unexpectedly before connecting to G-CLI");
+                print_app_output(config);
+                let stats = stop_process(process, None, config);
+                let code = config.exit_codes.code(failure);
+                // Scripts reading the JSON need to see this failure too.
+                if config.error_format == ErrorFormat::Json {
+                    error_report::print(config, &error, failure, code, Some(&stats));
+                }
+                return Ok(code);
+            }
+            Some(_) => {
+                print_app_output(config);
+                return Err(error).wrap_err("No connection established with application.");
+            }
+            None => return Err(error),
+        }
+    };
 
     process
         .set_connected()
@@ -76,9 +203,9 @@ fn gcli() -> Result<i32> {
     // 1. Action Loop - Recieves messages from inputs and takes appropriate actions.
     //                  Also writes a stop signal for other threads.
     // 2. Comms Loop - Recieve incoming comms from LabVIEW.
-    // 3. CtrlC Handler
-
-    let action_loop = ActionLoop::new();
+    // 3. Signal Handler - Ctrl+C and the termination signals.
+    // 4. Run Timeout - If a run timeout is set.
+    // The process monitor is already running and reports to the action loop.
 
     comms_loop::start(
         connection,
@@ -86,22 +213,198 @@ fn gcli() -> Result<i32> {
         action_loop.get_stop_signal(),
     );
 
-    signal_loop::start(action_loop.get_channel(), action_loop.get_stop_signal())?;
+    signal_loop::start(
+        signals,
+        action_loop.get_channel(),
+        action_loop.get_stop_signal(),
+    )?;
+
+    if let Some(timeout) = config.run_timeout {
+        timeout_loop::start(
+            action_loop.get_channel(),
+            action_loop.get_stop_signal(),
+            timeout,
+        )?;
+    }
 
     let exit = action_loop.run();
 
-    match exit {
+    if config.verbose {
+        print_app_output(config);
+    }
+
+    keep_for_detached(&exit, config, &mut isolation, &mut display);
+    Ok(stop_for_exit(exit, process, &exit_code_map, config))
+}
+
+/// A detached application is still using the isolated directories and virtual display so leave them in place.
+fn keep_for_detached(
+    exit: &ExitAction,
+    config: &cli::Configuration,
+    isolation: &mut Option<IsolatedRun>,
+    display: &mut Option<VirtualDisplay>,
+) {
+    if !matches!(exit, ExitAction::Detached(_)) || config.die_with_parent {
+        return;
+    }
+    if let Some(isolation) = isolation.take() {
+        isolation.keep();
+    }
+    if let Some(display) = display.take() {
+        display.keep();
+    }
+}
+
+/// Stops the process as required for how the run ended and returns the exit code for G-CLI.
+fn stop_for_exit(
+    exit: ExitAction,
+    process: MonitoredProcess,
+    exit_code_map: &ExitCodeMap,
+    config: &cli::Configuration,
+) -> i32 {
+    // A crash closes the connection too and the monitor can take a poll to notice the exit.
+    let exit = match exit {
+        ExitAction::CommsFailed if process.wait_for_exit(Duration::from_millis(500)) => {
+            debug!("The connection failed because the application exited");
+            ExitAction::AppTerminated
+        }
+        exit => exit,
+    };
+    let (failure, message, stats) = match exit {
         ExitAction::CleanExit(code) => {
-            process.stop(config.kill);
+            let code = exit_code_map.translate(code);
             debug!("Exiting G-CLI with exit code {}", code);
-            Ok(code)
+            stop_process(process, config.kill, config);
+            return code;
         }
-        ExitAction::ForcedExit => {
+        ExitAction::ForcedExit(signal) => {
             debug!("Recieved a signal to kill the process. Exiting and killing LabVIEW process");
-            process.stop(Some(Duration::from_millis(1)));
-            Ok(-1)
+            (
+                ProxyFailure::Signal(signal),
+                format!("Recieved signal {signal} so the application was killed"),
+                process.stop(Some(Duration::from_millis(1))),
+            )
         }
+        ExitAction::Cancelled(signal) => {
+            debug!("Recieved a signal to cancel. Terminating LabVIEW before killing it");
+            (
+                ProxyFailure::Signal(signal),
+                format!("Recieved signal {signal} so the application was cancelled"),
+                process.stop_gracefully(config.kill_grace),
+            )
+        }
+        ExitAction::Detached(signal) => {
+            if config.die_with_parent {
+                warn!("Detach was requested but LabVIEW will still close because of --die-with-parent");
+            }
+            debug!("Recieved a signal to detach. Exiting and leaving LabVIEW running");
+            let stats = if config.die_with_parent {
+                process.stop(None)
+            } else {
+                process.detach()
+            };
+            (
+                ProxyFailure::Signal(signal),
+                format!("Recieved signal {signal} so G-CLI detached from the application"),
+                stats,
+            )
+        }
+        ExitAction::TimedOut => {
+            let message = format!(
+                "The run timed out after {:?}",
+                config.run_timeout.unwrap_or_default()
+            );
+            error!("{}. Cancelling LabVIEW", message);
+            (
+                ProxyFailure::RunTimeout,
+                message,
+                process.cancel(config.kill_grace),
+            )
+        }
+        ExitAction::IdleTimeout => {
+            debug!("Cancelling the idle application");
+            let message = format!(
+                "The application sent nothing for {:?}",
+                config.idle_timeout.unwrap_or_default()
+            );
+            (
+                ProxyFailure::IdleTimeout,
+                message,
+                process.cancel(config.kill_grace),
+            )
+        }
+        ExitAction::CommsFailed => (
+            ProxyFailure::CommsError,
+            "The connection to the application failed before it sent an exit code".to_string(),
+            process.stop(config.kill),
+        ),
+        ExitAction::AppTerminated => {
+            let message = "The application terminated unexpectedly";
+            error!("{}", message);
+            (
+                ProxyFailure::AppTerminated,
+                message.to_string(),
+                process.stop(None),
+            )
+        }
+    };
+
+    if config.stats {
+        eprint!("{}", stats);
     }
+    report_failure(config, failure, message, Some(&stats))
+}
+
+/// Returns the exit code for a failure which ends the run without an error,
+/// reporting it when scripts are reading JSON as they won't see the log.
+fn report_failure(
+    config: &cli::Configuration,
+    failure: ProxyFailure,
+    message: String,
+    stats: Option<&ProcessStats>,
+) -> i32 {
+    let code = config.exit_codes.code(failure);
+    if config.error_format == ErrorFormat::Json {
+        error_report::print(config, &eyre!(message), failure, code, stats);
+    }
+    code
+}
+
+/// Failures where the launch may succeed if we try again.
+///
+/// Problems with the configuration, like a missing VI, will fail the same way every time.
+fn is_retryable(error: &Report) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<LabVIEWError>(),
+            Some(
+                LabVIEWError::ProcessLaunchFailed(_)
+                    | LabVIEWError::ServiceLocatorCommsError(_)
+                    | LabVIEWError::ServiceLocatorResponseError(_)
+            )
+        ) || matches!(
+            cause.downcast_ref::<CommsError>(),
+            Some(CommsError::WaitOnConnectionTimeOut(_) | CommsError::WaitOnConnectionAborted)
+        )
+    })
+}
+
+/// The delay before retrying after the attempt failed, doubling with each attempt.
+fn retry_delay(initial: Duration, attempt: u32) -> Duration {
+    initial.saturating_mul(1 << (attempt - 1).min(16))
+}
+
+/// Stops monitoring the process, killing it if requested, and prints the stats if enabled.
+fn stop_process(
+    process: MonitoredProcess,
+    kill: Option<Duration>,
+    config: &cli::Configuration,
+) -> ProcessStats {
+    let stats = process.stop(kill);
+    if config.stats {
+        eprint!("{}", stats);
+    }
+    stats
 }
 
 fn configure_logger(verbose: bool) -> Result<(), Report> {
@@ -128,14 +431,123 @@ fn configure_logger(verbose: bool) -> Result<(), Report> {
     Ok(())
 }
 
+/// Prints the output we captured from the application itself, if any.
+///
+/// This is where startup errors from LabVIEW, such as missing libraries, will appear.
+fn print_app_output(config: &cli::Configuration) {
+    if let Some(log_path) = &config.app_log {
+        match std::fs::read(log_path) {
+            Ok(output) if output.is_empty() => {
+                debug!("No output captured from the application.")
+            }
+            Ok(output) => eprintln!(
+                "Output captured from the application:\n{}",
+                String::from_utf8_lossy(&output)
+            ),
+            Err(e) => error!(
+                "Failed to read the application output log \"{}\": {}",
+                log_path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Starts a virtual display if the application won't have one.
+/// This is held until the end of the run so it is shut down after the process is stopped.
+fn start_virtual_display(config: &cli::Configuration) -> Result<Option<VirtualDisplay>> {
+    if !virtual_display::display_required() {
+        return Ok(None);
+    }
+
+    if config.no_virtual_display {
+        warn!("DISPLAY is not set so LabVIEW may fail to start. Remove --no-virtual-display to run it on a virtual display.");
+        return Ok(None);
+    }
+
+    let display = VirtualDisplay::start().wrap_err("Failed to start a virtual display")?;
+    Ok(Some(display))
+}
+
+/// Combines the display, isolation, environment files and command line variables for the application.
+fn app_environment(
+    config: &cli::Configuration,
+    isolation: Option<&IsolatedRun>,
+    display: Option<&VirtualDisplay>,
+) -> Result<EnvironmentOptions> {
+    let mut set = display.map(VirtualDisplay::environment).unwrap_or_default();
+    set.extend(isolation.map(IsolatedRun::environment).unwrap_or_default());
+    let mut environment = EnvironmentOptions {
+        set,
+        ..config.environment.clone()
+    };
+    for file in &config.env_files {
+        environment
+            .load_file(file)
+            .wrap_err("Failed to load the application environment")?;
+    }
+    environment
+        .set
+        .extend(config.environment.set.iter().cloned());
+    Ok(environment)
+}
+
+/// Combines the exit code map files and command line mappings.
+fn exit_code_map(config: &cli::Configuration) -> Result<ExitCodeMap> {
+    let mut map = ExitCodeMap {
+        codes: Vec::new(),
+        ..config.exit_code_map.clone()
+    };
+    for file in &config.exit_code_map_files {
+        map.load_file(file)?;
+    }
+    map.codes.extend(config.exit_code_map.codes.iter().copied());
+    Ok(map)
+}
+
+/// An isolated run always gets its own preference file so LabVIEW doesn't save to the shared one.
+fn lv_preferences(
+    config: &cli::Configuration,
+    isolation: Option<&IsolatedRun>,
+) -> Option<PreferenceOptions> {
+    match isolation {
+        Some(isolation) => {
+            let preferences = config.lv_preferences.clone().unwrap_or_default();
+            // Anything set on the command line still wins.
+            let mut overrides = isolation.preferences();
+            overrides.extend(preferences.overrides);
+            Some(PreferenceOptions {
+                overrides,
+                directory: Some(isolation.preference_dir()),
+                ..preferences
+            })
+        }
+        None => config.lv_preferences.clone(),
+    }
+}
+
 /// Launch the client process.
 ///
 /// Contains the logic to select different launch methods based on the type of file we are launching.
 fn launch_process(
     config: &cli::Configuration,
+    isolation: Option<&IsolatedRun>,
+    display: Option<&VirtualDisplay>,
     app_listener: &AppListener,
-) -> Result<labview::process::MonitoredProcess> {
+    action_loop: &ActionLoop,
+) -> Result<MonitoredProcess> {
     let launch_path = config.to_launch.clone();
+    let launch_options = LaunchOptions {
+        output_log: config.app_log.clone(),
+        termination: config.kill_policy,
+        kill_existing: config.kill_existing,
+        environment: app_environment(config, isolation, display)?,
+        working_dir: config.app_workdir.clone(),
+        preferences: lv_preferences(config, isolation),
+        app_args: config.app_args.clone(),
+        launcher: config.launcher.clone(),
+        die_with_parent: config.die_with_parent,
+    };
     let extension_as_str = launch_path.extension().map(|ext| {
         //allow panic here as I don't expect we will ever really hit it.
         ext.to_str().expect("Extension isn't valid UTF-8")
@@ -150,11 +562,25 @@ fn launch_process(
                 launch_path,
                 app_listener.port(),
                 config.allow_dialogs,
+                &launch_options,
+                action_loop.get_channel(),
+                action_loop.get_stop_signal(),
             )
             .wrap_err("Failed to Launch LabVIEW")
         }
         Some("exe") => {
-            launch_exe(launch_path, app_listener.port()).wrap_err("Failed to Launch Executable")
+            if config.lv_preferences.is_some() {
+                warn!("LabVIEW preferences are ignored when launching an executable.");
+            }
+
+            launch_exe(
+                launch_path,
+                app_listener.port(),
+                &launch_options,
+                action_loop.get_channel(),
+                action_loop.get_stop_signal(),
+            )
+            .wrap_err("Failed to Launch Executable")
         }
         None => {
             debug!("No extension in path. Assume it is a .vi");
@@ -169,6 +595,9 @@ fn launch_process(
                 launch_path,
                 app_listener.port(),
                 config.allow_dialogs,
+                &launch_options,
+                action_loop.get_channel(),
+                action_loop.get_stop_signal(),
             )
             .wrap_err("Failed to launch LabVIEW")
         }
@@ -193,9 +622,38 @@ fn find_install(
         None => system_installs.get_default(),
     };
 
-    let active_install = active_install.ok_or_else(|| eyre!("No LabVIEW install found."))?;
+    let active_install = active_install.ok_or(LabVIEWError::InstallNotFound)?;
     Ok(active_install.clone())
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles() {
+        let initial = Duration::from_secs(2);
+        assert_eq!(retry_delay(initial, 1), Duration::from_secs(2));
+        assert_eq!(retry_delay(initial, 3), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn retry_transient_failures() {
+        let timeout = Report::new(CommsError::WaitOnConnectionTimeOut(Duration::from_secs(1)))
+            .wrap_err("No connection established with application.");
+        assert!(is_retryable(&timeout));
+        assert!(is_retryable(&Report::new(
+            LabVIEWError::ServiceLocatorResponseError(500)
+        )));
+    }
+
+    #[test]
+    fn dont_retry_configuration_errors() {
+        let missing_vi = Report::new(LabVIEWError::ViDoesNotExist("test.vi".into()))
+            .wrap_err("Failed to launch the process.");
+        assert!(!is_retryable(&missing_vi));
+        assert!(!is_retryable(&Report::new(LabVIEWError::ManagedArgument(
+            "-pref".to_string()
+        ))));
+    }
+}
@@ -1,18 +1,60 @@
 use crate::action_loop::ActionMessage;
+use crate::exit_codes::SIGINT;
 use eyre::{Context, Result};
 use log::debug;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
 use std::time::Duration;
 
-/// Starts a thread that monitors a ctrlc or SIGINT event.
+/// What to do with the application when G-CLI receives a termination signal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SignalAction {
+    /// Send LabVIEW SIGTERM and kill it if it hasn't exited after the kill grace.
+    Cancel,
+    /// Kill LabVIEW straight away, the same as Ctrl+C.
+    Kill,
+    /// Stop G-CLI and leave LabVIEW running.
+    Detach,
+}
+
+/// The action for each of the termination signals other than Ctrl+C.
 ///
+/// These are only received on Unix.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SignalActions {
+    pub terminate: SignalAction,
+    pub hangup: SignalAction,
+    pub quit: SignalAction,
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            terminate: SignalAction::Kill,
+            hangup: SignalAction::Kill,
+            quit: SignalAction::Kill,
+        }
+    }
+}
+
+/// The signal handlers and the signals they have received.
 ///
-pub fn start(tx: Sender<ActionMessage>, stop: Arc<AtomicBool>) -> Result<()> {
-    //We will run a local thread to wait on a signal from the handler
-    //but also periodically check the stop.
+/// These are installed before anything is launched so a signal at any point replaces
+/// the default action of killing G-CLI, which would leave LabVIEW and our run resources behind.
+pub struct Signals {
+    ctrl_c: Receiver<()>,
+    #[cfg(unix)]
+    termination: signal_hook::iterator::Signals,
+    #[cfg(unix)]
+    actions: SignalActions,
+}
 
+/// Installs the handlers for a ctrlc or SIGINT event,
+/// and SIGTERM, SIGHUP and SIGQUIT on Unix.
+pub fn install(
+    #[cfg_attr(not(unix), allow(unused_variables))] actions: SignalActions,
+) -> Result<Signals> {
     let (local_tx, local_rx) = channel();
 
     ctrlc::set_handler(move || {
@@ -20,15 +62,66 @@ pub fn start(tx: Sender<ActionMessage>, stop: Arc<AtomicBool>) -> Result<()> {
     })
     .wrap_err("Should never set handler twice")?;
 
+    #[cfg(unix)]
+    let termination = {
+        use signal_hook::consts::{SIGHUP, SIGQUIT, SIGTERM};
+        signal_hook::iterator::Signals::new([SIGTERM, SIGHUP, SIGQUIT])
+            .wrap_err("Failed to register the termination signal handlers")?
+    };
+
+    Ok(Signals {
+        ctrl_c: local_rx,
+        #[cfg(unix)]
+        termination,
+        #[cfg(unix)]
+        actions,
+    })
+}
+
+impl Signals {
+    /// Takes a signal that has been received, if any, with the action configured for it.
+    ///
+    /// Ctrl+C is reported as SIGINT with the kill action.
+    pub fn try_next(&mut self) -> Option<(i32, SignalAction)> {
+        if self.ctrl_c.try_recv().is_ok() {
+            return Some((SIGINT, SignalAction::Kill));
+        }
+
+        #[cfg(unix)]
+        if let Some(signal) = self.termination.pending().next() {
+            return Some((signal, signal_action(signal, &self.actions)));
+        }
+
+        None
+    }
+}
+
+/// Starts a thread that passes the signals on to the action loop.
+///
+pub fn start(
+    #[cfg_attr(not(unix), allow(unused_mut))] mut signals: Signals,
+    tx: Sender<ActionMessage>,
+    stop: Arc<AtomicBool>,
+) -> Result<()> {
+    //We will run a local thread to wait on a signal from the handler
+    //but also periodically check the stop.
+
     std::thread::Builder::new()
         .name("Ctrl C Handler Thread".to_string())
         .spawn(move || {
             loop {
-                match local_rx.recv_timeout(Duration::from_millis(100)) {
+                match signals.ctrl_c.recv_timeout(Duration::from_millis(100)) {
                     Ok(_) => {
                         tx.send(ActionMessage::CtrlC).expect("Action loop gone?");
                     }
                     Err(RecvTimeoutError::Timeout) => {
+                        #[cfg(unix)]
+                        for signal in signals.termination.pending() {
+                            let action = signal_action(signal, &signals.actions);
+                            tx.send(ActionMessage::Signal(signal, action))
+                                .expect("Action loop gone?");
+                        }
+
                         //no message - just check stop.
                         if stop.load(Ordering::Relaxed) {
                             break;
@@ -44,3 +137,15 @@ pub fn start(tx: Sender<ActionMessage>, stop: Arc<AtomicBool>) -> Result<()> {
 
     Ok(())
 }
+
+/// Looks up the configured action for a signal we registered.
+#[cfg(unix)]
+fn signal_action(signal: i32, actions: &SignalActions) -> SignalAction {
+    use signal_hook::consts::{SIGHUP, SIGQUIT};
+
+    match signal {
+        SIGHUP => actions.hangup,
+        SIGQUIT => actions.quit,
+        _ => actions.terminate,
+    }
+}
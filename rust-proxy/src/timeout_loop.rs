@@ -0,0 +1,31 @@
+use crate::action_loop::ActionMessage;
+use eyre::Result;
+use log::debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Starts a thread that tells the action loop if the run lasts longer than the timeout.
+///
+/// The thread stops early if the stop signal is set.
+pub fn start(tx: Sender<ActionMessage>, stop: Arc<AtomicBool>, timeout: Duration) -> Result<()> {
+    let end_time = Instant::now() + timeout;
+
+    std::thread::Builder::new()
+        .name("Run Timeout Thread".to_string())
+        .spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let now = Instant::now();
+                if now >= end_time {
+                    tx.send(ActionMessage::RunTimeout)
+                        .expect("Action loop gone?");
+                    break;
+                }
+                std::thread::sleep((end_time - now).min(Duration::from_millis(100)));
+            }
+            debug!("Run Timeout Stopped.");
+        })?;
+
+    Ok(())
+}